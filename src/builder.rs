@@ -0,0 +1,151 @@
+use crate::D;
+use crate::error::DiceError;
+use crate::headless::{self, RollResult};
+use crate::input_handling::{self, Clamp, Code, Command, Factor, Keep, Modifier, Reroll, ThrowOptions};
+
+// builds rolls in code instead of writing them out for the parser, for programs using this as a library:
+//     Roll::new().dice(2, D::Six).modifier(3).label("fire damage").dice(1, D::Twenty).advantage().roll()
+// each call to dice() starts a command, and the calls after it change that command
+
+#[derive(Clone, Default)]
+pub struct Roll {
+    commands: Vec<Command>,
+    early: bool, // something was set before there were any dice to set it on
+}
+
+impl Roll {
+    pub fn new() -> Roll {
+        Roll::default()
+    }
+
+    pub fn dice(mut self, count: u16, kind: D) -> Roll { // starts a command, e.g. dice(2, D::Six) for "2d6"
+        self.commands.push(Command {
+            label: None,
+            code: Code::Normal,
+            coefficient: count,
+            kind,
+            modifiers: Vec::new(),
+            keep: Keep::All,
+            explode: false,
+            reroll: Reroll::Never,
+            target: None,
+            botch: false,
+            glitch: false,
+            factor: Factor::One,
+            clamp: Clamp::default(),
+            negative: false,
+        });
+        self
+    }
+
+    pub fn minus(self, count: u16, kind: D) -> Roll { // starts a command that's taken off the sum, e.g. the "-1d4" in "2d8-1d4"
+        self.dice(count, kind).set(|command| command.negative = true)
+    }
+
+    pub fn modifier(self, value: i16) -> Roll { // can be given more than once, like "+3+2"
+        self.set(|command| command.modifiers.push(Modifier { value, name: None }))
+    }
+
+    pub fn label(self, label: &str) -> Roll {
+        self.set(|command| command.label = Some(label.trim().to_string()))
+    }
+
+    pub fn advantage(self) -> Roll {
+        self.set(|command| command.code = Code::Advantage(2))
+    }
+
+    pub fn disadvantage(self) -> Roll {
+        self.set(|command| command.code = Code::Disadvantage(2))
+    }
+
+    pub fn keep(self, keep: Keep) -> Roll { // e.g. keep(Keep::Highest(3)) for "kh3"
+        self.set(|command| command.keep = keep)
+    }
+
+    pub fn explode(self) -> Roll {
+        self.set(|command| command.explode = true)
+    }
+
+    pub fn reroll(self, reroll: Reroll) -> Roll { // e.g. reroll(Reroll::Once(1)) for "ro1"
+        self.set(|command| command.reroll = reroll)
+    }
+
+    pub fn factor(self, factor: Factor) -> Roll { // e.g. factor(Factor::Divide(2)) for "/2"
+        self.set(|command| command.factor = factor)
+    }
+
+    pub fn build(self) -> Result<(Vec<D>, Vec<Command>), DiceError> { // the dice and commands, just as generate_dice() would give them for the same roll written out
+        if self.early {
+            return Err(DiceError::Needs { what: "What's done to dice", needs: "the dice added first" });
+        }
+        if self.commands.is_empty() {
+            return Err(DiceError::Needs { what: "A roll", needs: "some dice" });
+        }
+        let mut command_log = self.commands;
+        for command in command_log.iter_mut() {
+            if command.kind == D::PercentOnes {
+                return Err(DiceError::Needs { what: "A percentile roll", needs: "D::PercentTens for a d100, or D::PercentHundreds for a d1000" }); // the ones die comes with them
+            }
+            if matches!(command.kind, D::PercentTens | D::PercentHundreds) && command.code == Code::Normal {
+                command.code = Code::Percentile;
+            }
+            input_handling::validate(command)?;
+        }
+        input_handling::load_dice(command_log, &ThrowOptions::default())
+    }
+
+    pub fn roll(self) -> Result<RollResult, DiceError> { // throws it without the table or terminal, like dice::roll()
+        let options = ThrowOptions::default();
+        let (_, command_log) = self.build()?;
+        let commands = headless::roll(&command_log, &options)?;
+        Ok(headless::finish(&options, commands))
+    }
+
+    fn set(mut self, change: impl FnOnce(&mut Command)) -> Roll { // changes the latest command
+        match self.commands.last_mut() {
+            Some(command) => change(command),
+            None => self.early = true,
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn same_as(built: Roll, written: &str) { // the builder gives what the parser does for the roll written out, and throws it the same way
+        let (built_dice, built_log) = built.build().unwrap_or_else(|error| panic!("'{written}' should build: {error}"));
+        let (dice, log) = input_handling::generate_dice(written.to_string(), &ThrowOptions::default()).unwrap();
+        assert_eq!(built_dice, dice, "{written}");
+        assert!(built_log == log, "{written}");
+        let throw = |log: &[Command]| headless::finish(&ThrowOptions::default(), headless::roll_with(log, &ThrowOptions::default(), &mut StdRng::seed_from_u64(7)).unwrap());
+        assert_eq!(throw(&built_log).line, throw(&log).line, "{written}");
+    }
+
+    #[test]
+    fn matches_the_parser() {
+        same_as(Roll::new().dice(2, D::Six).modifier(3), "2d6+3");
+        same_as(Roll::new().dice(2, D::Six).modifier(3).label("fire damage").dice(1, D::Twenty).advantage(), "2d6+3 # fire damage, adv d20");
+        same_as(Roll::new().dice(4, D::Six).keep(Keep::Highest(3)), "4d6kh3");
+        same_as(Roll::new().dice(3, D::Six).explode(), "3d6!");
+        same_as(Roll::new().dice(2, D::Ten).reroll(Reroll::Once(1)), "2d10ro1");
+        same_as(Roll::new().dice(8, D::Six).factor(Factor::Divide(2)), "8d6/2");
+        same_as(Roll::new().dice(2, D::Eight).minus(1, D::Four), "2d8-1d4");
+        same_as(Roll::new().dice(1, D::PercentTens), "d100");
+        same_as(Roll::new().dice(1, D::Twenty).disadvantage().modifier(2).modifier(-1), "disadv d20+2-1");
+    }
+
+    #[test]
+    fn rejects_what_the_parser_would() {
+        assert!(Roll::new().modifier(3).dice(1, D::Six).build().is_err()); // nothing to modify yet
+        assert!(Roll::new().build().is_err());
+        assert!(Roll::new().dice(0, D::Six).build().is_err());
+        assert!(Roll::new().dice(1, D::Custom(0)).build().is_err());
+        assert!(Roll::new().dice(1, D::PercentOnes).build().is_err());
+        assert!(Roll::new().dice(4, D::Six).keep(Keep::Highest(4)).build().is_err());
+    }
+}
@@ -1,62 +1,95 @@
-use crate::directions::Direction;
-use crate::util::*;
+use std::fmt;
+use std::{sync::mpsc::Sender, thread, time::Duration};
 
-use std::sync::mpsc::Sender;
-use std::time::Duration;
-use std::thread;
+use crate::directions::Direction;
+use crate::rng::RngSource;
+use crate::weighted;
 
-use termion::terminal_size;
+use rand::Rng;
 
-use rand::{Rng, thread_rng};
+// structs representing dice objects, their types, and their behaviour.
+// a die knows nothing of the terminal: it bounces around an area of the given size and reports where it is,
+// so the table (or any other frontend) can draw it. it takes any generator, e.g. a seeded or mock one in tests:
+//     Die::new(0, D::Twenty, tx, StdRng::seed_from_u64(7), (80, 24)).roll();
 
-// structs representing dice objects, their types, and their behaviour
 
-pub struct Die {
+pub struct Die<R: Rng = RngSource> {
     id: usize,
     kind: D,
     face_up: u16,
-    tx: Sender<(usize, u16, (u16, u16))>, // id, face up, and position
+    tx: Sender<RollEvent>,
+    area: (u16, u16), // (cols, rows) of the surface the die bounces around, 1-based like Goto
     position: (u16, u16), // (col, row)
     speed: i16,
     direction: Direction,
+    rng: R, // everything about the die's roll comes from here, so the same seed gives the same faces and bounces
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RollEvent { // what a die reports as it's thrown, for whatever is drawing it
+    Started { id: usize, kind: D, face: u16, position: (u16, u16) }, // thrown onto the table
+    Moved { id: usize, face: u16, position: (u16, u16) }, // one flip along its way
+    Settled { id: usize, face: u16, position: (u16, u16) }, // come to rest (dice set down without rolling only send this)
+}
+
+impl RollEvent {
+    pub fn die(&self) -> (usize, u16, (u16, u16)) { // id, face up, and position of the die the event is about
+        match *self {
+            RollEvent::Started { id, face, position, .. } | RollEvent::Moved { id, face, position } | RollEvent::Settled { id, face, position } => (id, face, position),
+        }
+    }
 }
 
-impl Die {
-    pub fn new(id: usize, kind: D, tx: Sender<(usize, u16, (u16, u16))>) -> Die {
+impl<R: Rng> Die<R> {
+    pub fn new(id: usize, kind: D, tx: Sender<RollEvent>, mut rng: R, area: (u16, u16)) -> Die<R> { // the kind needs at least one side
         const MAX_INIT_SPEED: i16 = 120; // in flips (position shifs) per second
         const MIN_INIT_SPEED: i16 = 60;
         Die {
             id,
             kind,
-            face_up: kind.flip(),
+            face_up: kind.flip(&mut rng),
             tx,
-            position: Die::spawn_point(),
-            speed: thread_rng().gen_range(MIN_INIT_SPEED..=MAX_INIT_SPEED),
-            direction: Direction::random(),
+            area,
+            position: Die::spawn_point(&mut rng, area),
+            speed: rng.gen_range(MIN_INIT_SPEED..=MAX_INIT_SPEED),
+            direction: Direction::random(&mut rng),
+            rng,
         }
     }
 
-    fn spawn_point() -> (u16, u16) {
-        let (h, v) = terminal_size().unwrap();
+    fn spawn_point(rng: &mut R, area: (u16, u16)) -> (u16, u16) {
+        let (h, v) = area;
         let h_radius = h / 8; // return a spawn poing somewhere within the central quarter of the window
         let v_radius = v / 8;
-        let centre = terminal_centre();
-        let col = thread_rng().gen_range(centre.0 - h_radius ..= centre.0 + h_radius);
-        let row = thread_rng().gen_range(centre.1 - v_radius ..= centre.1 + v_radius);
+        let centre = (h / 2, v / 2);
+        let col = rng.gen_range(centre.0 - h_radius ..= centre.0 + h_radius);
+        let row = rng.gen_range(centre.1 - v_radius ..= centre.1 + v_radius);
         (col, row)
     }
 
     pub fn roll(&mut self) {
         const STOP_SPEED: i16 = 0; // seems to strike a good balance of slowing but not hanging
+        self.tx.send(RollEvent::Started { id: self.id, kind: self.kind, face: self.face_up, position: self.position }).unwrap();
         while self.speed > STOP_SPEED {
-            self.face_up = self.kind.flip();
+            self.face_up = self.kind.flip(&mut self.rng);
             self.detect_wall(); // detects walls and changes direction if necessary
             self.movement(); // changes position
             // self._bounds_check(); // may not be necessary -> uncomment if wall bounces get buggy
-            self.tx.send((self.id, self.face_up, self.position)).unwrap();
+            self.tx.send(RollEvent::Moved { id: self.id, face: self.face_up, position: self.position }).unwrap();
             thread::sleep(Duration::from_millis(self.flip_time()));
             self.friction(); // needs to go after sleep in order for some rolls not to hang
         }
+        self.tx.send(RollEvent::Settled { id: self.id, face: self.face_up, position: self.position }).unwrap(); // let the table know this die has come to rest
+    }
+
+    pub fn settle(&mut self) { // sets the die down in its place in a grid at the centre without rolling it, for when the animation is off
+        const PER_ROW: usize = 10;
+        const SPACING: u16 = 6; // room for the widest face ("TAILS") and a gap
+        let (col, row) = (self.area.0 / 2, self.area.1 / 2);
+        let left = col.saturating_sub(SPACING * PER_ROW as u16 / 2).max(1);
+        let top = row.saturating_sub(4).max(1);
+        self.position = (left + (self.id % PER_ROW) as u16 * SPACING, top + (self.id / PER_ROW) as u16 * 2);
+        self.tx.send(RollEvent::Settled { id: self.id, face: self.face_up, position: self.position }).unwrap();
     }
 
     fn movement(&mut self) { // moves the die one square along its current trajectory
@@ -80,10 +113,8 @@ impl Die {
 
     fn detect_wall(&mut self) {
         let (l_wall, ceiling): (u16, u16) = (1, 1); // because Goto is 1-based
-        let (mut r_wall, floor) = terminal_size().unwrap();
-        if is_two_digits(self.face_up, self.kind) {
-            r_wall -= 1; // helps prevent overflow of 2-digit die
-        }
+        let (mut r_wall, floor) = self.area;
+        r_wall -= self.kind.width(self.face_up) - 1; // helps prevent overflow of dice wider than one character
         
         // is the die about to collide with a wall given its current position and direction?
         match self.position {
@@ -157,8 +188,8 @@ impl Die {
 
     fn bounce(&mut self, wall: bool) { // wall: whether the collision is against vertical surface or not
         const REDIRECT_CHANCE: f64 = 5.0; // reciprocal of chance for altered trajectory
-        let redirect: bool = thread_rng().gen_bool(1.0 / REDIRECT_CHANCE); // set to zero to remove redirections
-        let option: bool = thread_rng().gen_bool(1.0 / 2.0); // coin toss between two possible altered trajectories
+        let redirect: bool = self.rng.gen_bool(1.0 / REDIRECT_CHANCE); // set to zero to remove redirections
+        let option: bool = self.rng.gen_bool(1.0 / 2.0); // coin toss between two possible altered trajectories
         
         self.direction = match self.direction {
             Direction::Up => match (redirect, option) {
@@ -214,7 +245,7 @@ impl Die {
     }
 
     fn _bounds_check(&mut self) { // in case terminal gets resized smaller
-        let (max_col, max_row) = terminal_size().unwrap();
+        let (max_col, max_row) = self.area;
         let (col, row) = self.position;
         if col > max_col {
             self.position.0 = max_col;
@@ -225,22 +256,32 @@ impl Die {
     }
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum D {
     Two,
     Four,
     Six,
+    Eight,
     Ten,
     Twelve,
     Twenty,
     PercentTens, // use Tens as the one that the value input parser uses to communicate percentile roll
     PercentOnes,
+    PercentHundreds, // likewise for d1000, which adds a hundreds die to the other two
+    Custom(u16), // any other side count, e.g. d3 or d30
+    Weighted(u16), // a die with odds of its own, defined by name (see weighted.rs)
+    Fudge, // faces 1-3 stand for -, blank, and +
+    Coin, // face 1 is heads, face 2 tails
 }
 
 impl D {
-    fn flip(&self) -> u16 { // generates a new number to facing up depending on D type
-        let value = thread_rng().gen_range(1..=self.value());
+    pub fn flip<R: Rng + ?Sized>(&self, rng: &mut R) -> u16 { // generates a new number to facing up depending on D type
+        let value = match self {
+            D::Weighted(id) => return weighted::flip(*id, rng),
+            _ => rng.gen_range(1..=self.value()),
+        };
         match self {
+            D::PercentHundreds => 100 * (value - 1), // 0-900, mod 100
             D::PercentTens => 10 * (value - 1), // 0-90, mod 10
             D::PercentOnes => value - 1, // 0-9
             _ => value, // all other cases
@@ -249,13 +290,24 @@ impl D {
 
     fn acceleration(&self) -> i16 { // returns the speed lost per flip for each D type
         match self {
-            D::Two => -10,
+            D::Two | D::Coin => -10,
             D::Four => -7,
             D::Six => -4,
+            D::Eight => -3,
             D::Ten => -3,
             D::Twelve => -2,
             D::Twenty => -1,
-            D::PercentTens | D:: PercentOnes => -3,
+            D::PercentTens | D:: PercentOnes | D::PercentHundreds => -3,
+            D::Fudge => -4,
+            D::Custom(sides) => match sides { // roughly in line with the standard dice above
+                0..=2 => -10,
+                3..=4 => -7,
+                5..=6 => -4,
+                7..=10 => -3,
+                11..=12 => -2,
+                _ => -1,
+            },
+            D::Weighted(id) => D::Custom(weighted::sides(*id)).acceleration(),
         }
     }
 
@@ -264,11 +316,17 @@ impl D {
             D::Two => 2,
             D::Four => 4,
             D::Six => 6,
+            D::Eight => 8,
             D::Ten => 10,
             D::Twelve => 12,
             D::Twenty => 20,
             D::PercentTens => 10,
             D::PercentOnes => 10,
+            D::PercentHundreds => 10,
+            D::Custom(sides) => *sides,
+            D::Weighted(id) => weighted::sides(*id),
+            D::Fudge => 3,
+            D::Coin => 2,
         }
     }
 
@@ -277,11 +335,61 @@ impl D {
             D::Two => 2,
             D::Four => 4,
             D::Six => 6,
+            D::Eight => 8,
             D::Ten => 10,
             D::Twelve => 12,
             D::Twenty => 20,
             D::PercentTens => 100,
             D::PercentOnes => 100, // not actually needed so don't worry
+            D::PercentHundreds => 1000,
+            D::Custom(sides) => *sides,
+            D::Weighted(id) => weighted::sides(*id),
+            D::Fudge => 3, // likewise, Display writes "F" instead
+            D::Coin => 2,
+        }
+    }
+
+    pub fn label(&self, face: u16) -> String { // how a face is drawn on the table and graph
+        match (self, face) {
+            (D::PercentHundreds, 0) => String::from("000"),
+            (D::PercentTens, 0) => String::from("00"),
+            (D::Fudge, 1) => String::from("-"),
+            (D::Fudge, 2) => String::from(" "),
+            (D::Fudge, _) => String::from("+"),
+            (D::Coin, 1) => String::from("H"),
+            (D::Coin, _) => String::from("T"),
+            _ => face.to_string(),
+        }
+    }
+
+    pub fn table_label(&self, face: u16) -> String { // how a face is drawn while it's out on the table, where there's room to spell coins out
+        match (self, face) {
+            (D::Coin, 1) => String::from("HEADS"),
+            (D::Coin, _) => String::from("TAILS"),
+            _ => self.label(face),
+        }
+    }
+
+    pub fn width(&self, face: u16) -> u16 { // columns a face takes up on the table
+        self.table_label(face).chars().count() as u16
+    }
+
+    pub fn score(&self, face: u16) -> i16 { // what a face counts for in totals
+        match self {
+            D::Fudge => face as i16 - 2, // -1, 0, or +1
+            D::Coin => 2 - face as i16, // heads count 1
+            _ => face as i16,
+        }
+    }
+}
+
+impl fmt::Display for D { // the part of a command after the 'd'
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            D::Fudge => write!(f, "F"),
+            D::Coin => write!(f, "C"),
+            D::Weighted(id) => write!(f, "{}", weighted::name(*id)),
+            _ => write!(f, "{}", self.as_number()),
         }
     }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use rand::{Rng, thread_rng};
+use rand::Rng;
 
 // used by dice objects to represent their direction of movement
 
@@ -15,8 +15,8 @@ pub enum Direction {
 }
 
 impl Direction {
-    pub fn random() -> Direction {
-        match thread_rng().gen_range(1..=8) {
+    pub fn random(rng: &mut impl Rng) -> Direction {
+        match rng.gen_range(1..=8) {
             1 => Direction::Up,
             2 => Direction::Down,
             3 => Direction::Left,
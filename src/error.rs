@@ -0,0 +1,63 @@
+use std::error::Error;
+use std::fmt;
+
+// what can go wrong reading or showing a roll, so programs using this as a library can tell failures apart.
+// the messages are the ones the prompt has always printed
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DiceError {
+    InvalidCoefficient, // the number of dice before the 'd' couldn't be read, e.g. "xd6"
+    UnknownDie { input: String }, // what was left once everything else was read off the command, e.g. "d" or "dx"
+    Malformed { part: &'static str }, // a part of the command that couldn't be read, e.g. "Modifier" or "Keep/drop"
+    LimitExceeded { what: &'static str }, // a number too big for the table and graph, e.g. "Coefficient" or "Dice total"
+    TooManyDice { limit: usize },
+    TerminalTooSmall { needed: (u16, u16) }, // columns and rows the graph needs
+    OutOfRange { what: &'static str, min: u16, max: u16 }, // a number a prefix takes, e.g. the "Time limit" of a timed roll
+    ZeroCoefficient,
+    NoSides, // a custom die with zero sides, which has nothing to land on
+    TooManyModifiers { limit: usize },
+    LabelTooLong { limit: usize }, // counted in characters
+    KeepCount, // keeping or dropping none of the dice, or all of them
+    KeepMiddle, // keep-middle on an even number of dice, or fewer than three
+    NotOnDie { what: &'static str }, // a face no side of the die shows, e.g. "Reroll face" or "Success target"
+    Endless { what: &'static str }, // a one-sided die asked to "explode" or "reroll", which would never stop
+    MinAboveMax,
+    DivideByZero,
+    NotAllowed { what: &'static str }, // something the roll's prefix or die can't have done to it, e.g. "keep or drop dice"
+    Conflict { first: &'static str, second: &'static str }, // two things that can't go on the same roll, e.g. "A skill shot" and "a time limit"
+    Needs { what: &'static str, needs: &'static str }, // a mode missing something it has to have, e.g. "Lock mode" and "a single roll of 2 to 9 dice"
+    UnknownFlag { flag: String },
+    NeedsTable, // a mode the headless rolls can't play out
+}
+
+impl fmt::Display for DiceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiceError::InvalidCoefficient => write!(f, "Coefficient error"),
+            DiceError::UnknownDie { input } if input.trim().is_empty() => write!(f, "Die type error"),
+            DiceError::UnknownDie { input } => write!(f, "Die type error: '{}'", input.trim()),
+            DiceError::Malformed { part } => write!(f, "{part} error"),
+            DiceError::LimitExceeded { what } => write!(f, "{what} limit exceeded"),
+            DiceError::TooManyDice { .. } => write!(f, "Cannot roll this many die"),
+            DiceError::TerminalTooSmall { .. } => write!(f, "Window too small to display results"),
+            DiceError::OutOfRange { what, min, max } => write!(f, "{what} must be between {min} and {max}"),
+            DiceError::ZeroCoefficient => write!(f, "Coefficient cannot be zero"),
+            DiceError::NoSides => write!(f, "A die needs at least one side"),
+            DiceError::TooManyModifiers { limit } => write!(f, "Too many modifiers on one command (at most {limit})"),
+            DiceError::LabelTooLong { limit } => write!(f, "Label can be at most {limit} characters"),
+            DiceError::KeepCount => write!(f, "Keep/drop count must be less than the coefficient"),
+            DiceError::KeepMiddle => write!(f, "Keep-middle needs an odd number of dice, at least 3"),
+            DiceError::NotOnDie { what } => write!(f, "{what} is not on this die"),
+            DiceError::Endless { what } => write!(f, "A one-sided die would {what} forever"),
+            DiceError::MinAboveMax => write!(f, "Min cannot be greater than max"),
+            DiceError::DivideByZero => write!(f, "Cannot multiply or divide by zero"),
+            DiceError::NotAllowed { what } => write!(f, "You cannot {what} on this roll"),
+            DiceError::Conflict { first, second } => write!(f, "{first} cannot go with {second}"),
+            DiceError::Needs { what, needs } => write!(f, "{what} needs {needs}"),
+            DiceError::UnknownFlag { flag } => write!(f, "Unknown roll flag '{flag}' -- try --fast, --blind, --seed, or --label"),
+            DiceError::NeedsTable => write!(f, "Lock mode, skill shots, timed rolls, split damage, and hordes need the table"),
+        }
+    }
+}
+
+impl Error for DiceError {}
@@ -0,0 +1,133 @@
+use crate::die::RollEvent;
+
+use std::collections::VecDeque;
+use std::io::stdin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use termion::event::Key;
+use termion::input::TermRead;
+
+// everything the table and prompt wait on, through one channel: the keyboard is read on a thread of its own
+// for the life of the program, dice rolling on theirs send what they do into the same channel, and so does
+// a ticker while anything is timed. only one Events should be made -- a second would be left fighting the first over stdin
+
+pub enum Event {
+    Key(Key),
+    Tick, // another interval has passed, while tick_every() has a ticker running
+    Die(RollEvent),
+    Closed, // stdin ended, so no more keys are coming
+}
+
+pub struct Events {
+    tx: Sender<Event>,
+    rx: Receiver<Event>,
+    held: VecDeque<Event>, // arrived while waiting on something else (e.g. keys pressed while the dice roll), for the next wait
+    closed: bool,
+    ticker: Option<Arc<AtomicBool>>, // cleared to stop the running ticker
+}
+
+impl Events {
+    pub fn new() -> Events { // starts reading the keyboard
+        let (tx, rx) = channel();
+        let keys = tx.clone();
+        thread::spawn(move || {
+            for key in stdin().keys() {
+                let Ok(key) = key else { break };
+                if keys.send(Event::Key(key)).is_err() {
+                    return; // nobody's listening any more
+                }
+            }
+            keys.send(Event::Closed).ok();
+        });
+        Events { tx, rx, held: VecDeque::new(), closed: false, ticker: None }
+    }
+
+    pub fn wait(&mut self) -> Event { // the next thing to happen, or Closed straight away once stdin has ended (and nothing else is waiting)
+        if let Some(event) = self.held.pop_front() {
+            return event;
+        }
+        if self.closed && self.ticker.is_none() {
+            return Event::Closed;
+        }
+        match self.rx.recv().expect("Events keeps a sender, so the channel stays open") {
+            Event::Closed => {
+                self.closed = true;
+                Event::Closed
+            },
+            event => event,
+        }
+    }
+
+    pub fn key(&mut self) -> Option<Key> { // waits for a key press, or None once stdin has ended
+        loop {
+            match self.wait() {
+                Event::Key(key) => return Some(key),
+                Event::Closed => return None,
+                Event::Tick | Event::Die(_) => (), // nothing to do with keys
+            }
+        }
+    }
+
+    pub fn tick_every(&mut self, interval: Option<Duration>) { // starts sending a Tick every interval, for anything timed (a countdown, the skill shot's marker), or stops with None
+        if let Some(ticking) = self.ticker.take() {
+            ticking.store(false, Ordering::Relaxed);
+        }
+        while let Ok(event) = self.rx.try_recv() { // ticks from before don't count towards the new timing, but anything else still does
+            match event {
+                Event::Tick => (),
+                Event::Closed => self.closed = true,
+                event => self.held.push_back(event),
+            }
+        }
+        let Some(interval) = interval else { return };
+        let ticking = Arc::new(AtomicBool::new(true));
+        let (running, ticks) = (ticking.clone(), self.tx.clone());
+        thread::spawn(move || {
+            loop {
+                thread::sleep(interval);
+                if !running.load(Ordering::Relaxed) || ticks.send(Event::Tick).is_err() {
+                    break;
+                }
+            }
+        });
+        self.ticker = Some(ticking);
+    }
+
+    pub fn die(&mut self) -> RollEvent { // waits for the next thing a rolling die does, keeping any keys pressed meanwhile
+        loop {
+            match self.rx.recv().expect("Events keeps a sender, so the channel stays open") {
+                Event::Die(event) => return event,
+                Event::Tick => (),
+                Event::Closed => self.closed = true,
+                event => self.held.push_back(event),
+            }
+        }
+    }
+
+    pub fn line(&mut self) -> String { // a line typed at the terminal as it normally behaves (echoed and edited by it), ending in '\n' like read_line()
+        let mut line = String::new();
+        while let Some(key) = self.key() {
+            if let Key::Char(c) = key {
+                line.push(c);
+                if c == '\n' {
+                    break;
+                }
+            }
+        }
+        line
+    }
+
+    pub(crate) fn sender(&self) -> Sender<Event> { // for the dice, to send what they do
+        self.tx.clone()
+    }
+}
+
+impl Default for Events {
+    fn default() -> Events {
+        Events::new()
+    }
+}
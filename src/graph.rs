@@ -1,5 +1,7 @@
+use crate::input_handling::Modifier;
 use crate::util::*;
 
+use std::fmt::Display;
 use std::io::{Write, Stdout};
 
 use termion::raw::RawTerminal;
@@ -33,7 +35,7 @@ pub struct Graph {
 impl Graph {
     pub fn new(height: usize) -> Graph {
         let (col, row) = terminal_centre();
-        let divider = "----------------------------------";
+        let divider = "------------------------------------";
         let mut top_row = row.checked_sub(height as u16 / 2).unwrap_or(0);
         if top_row < 1 {
             top_row = 1; // terminal ceiling starts at 1
@@ -44,11 +46,11 @@ impl Graph {
             width: divider.len() as u16 + 2,
             height: height as u16,
 
-            command_col: col - 17, // max command width is 8 characters
-            arrow_col: col - 8, // arrows are 2 characters
-            result_col: col - 5, // individual results are max 2 characters
+            command_col: col - 19, // max command width is 8 characters
+            arrow_col: col - 10, // arrows are 2 characters
+            result_col: col - 7, // individual results are max 4 characters, and a '!' or '*' mark
             big_arrow_col: col - 2, // =>, 2 characters
-            running_col: col + 1, // max running total is in theory 99 x 20 = 1,980 (4 characters)
+            running_col: col + 1, // max running total is 9,999 (4 characters), however many sides the dice have
             modifier_col: col + 6, // modifiers are in theory max 4 characters including sign and space
             equals_col: col + 11, // =, 1 character
             sum_col: col + 13, // max 4 characters
@@ -72,7 +74,10 @@ impl Graph {
     }
 
     pub fn print_header(&mut self, screen: &mut RawTerminal<AlternateScreen<Stdout>>, label: &str) {
-        let header = "Rolls    Results       Mod  Total";
+        self.print_columns(screen, label, "Rolls    Results         Mod  Total");
+    }
+
+    pub fn print_columns(&mut self, screen: &mut RawTerminal<AlternateScreen<Stdout>>, label: &str, header: &str) { // the header with column names of its own, for tables laid out differently (horde attacks)
         self.label.push_str(label);
         write!(screen, "{}{header}{}{}{}{label}",
             Goto(self.command_col, self.top_row + 2),
@@ -82,10 +87,29 @@ impl Graph {
         ).unwrap();
     }
 
+    pub fn print_row(&mut self, screen: &mut RawTerminal<AlternateScreen<Stdout>>, row: &str) { // prints a whole row of a compact table and closes it off with the divider, which the next row overwrites
+        write!(screen, "{}{}{}{row}{}{}",
+            Goto(self.command_col, self.command_row),
+            " ".repeat(self.divider.len()), // rub out the last row's divider first
+            Goto(self.command_col, self.command_row),
+            Goto(self.command_col, self.command_row + 1),
+            self.divider
+        ).unwrap();
+
+        self.command_row += 1;
+        self.running_row = self.command_row; // the footer goes under the divider
+    }
+
     pub fn print_command(&mut self, screen: &mut RawTerminal<AlternateScreen<Stdout>>, command: &str) {
         write!(screen, "{}{command}", Goto(self.command_col, self.command_row)).unwrap();
     }
 
+    pub fn print_label(&mut self, screen: &mut RawTerminal<AlternateScreen<Stdout>>, label: &str, line: usize) { // prints a command's label under it, cut to fit before the arrows
+        const LABEL_WIDTH: usize = 8;
+        let label: String = label.chars().take(LABEL_WIDTH).collect();
+        write!(screen, "{}{label}", Goto(self.command_col, self.command_row + line as u16)).unwrap();
+    }
+
     pub fn goto_result_line(&mut self, screen: &mut RawTerminal<AlternateScreen<Stdout>>, line: usize) {
         write!(screen, "{}->{}", // draw the arrow and go to the line where to print the next result
             Goto(self.arrow_col, self.command_row + line as u16),
@@ -93,7 +117,29 @@ impl Graph {
         ).unwrap();
     }
 
-    pub fn print_totals(&mut self, screen: &mut RawTerminal<AlternateScreen<Stdout>>, total: u16, modifier: i16) { // prints the total/modifier/sum line for a command
+    pub fn print_step(&mut self, screen: &mut RawTerminal<AlternateScreen<Stdout>>, total: i16, step: &impl Display) { // prints the dice total and what's done to it (multiplied/divided, clamped), on its own line above the totals
+        write!(screen, "{}=>{}{total}{}{step}",
+            Goto(self.big_arrow_col, self.running_row),
+            Goto(self.running_col, self.running_row),
+            Goto(self.modifier_col, self.running_row),
+        ).unwrap();
+
+        self.running_row += 1;
+    }
+
+    pub fn print_modifier(&mut self, screen: &mut RawTerminal<AlternateScreen<Stdout>>, modifier: &Modifier) { // prints one of a command's modifiers and its name on its own line above the totals
+        const NAME_WIDTH: usize = 7; // room left before the end of the divider
+        let sign = if modifier.value >= 0 { '+' } else { '-' };
+        let name: String = modifier.name.iter().flat_map(|name| name.chars()).take(NAME_WIDTH).collect();
+        write!(screen, "{}{sign} {} {name}",
+            Goto(self.modifier_col, self.running_row),
+            modifier.value.abs(),
+        ).unwrap();
+
+        self.running_row += 1;
+    }
+
+    pub fn print_totals(&mut self, screen: &mut RawTerminal<AlternateScreen<Stdout>>, total: i16, modifier: i16) { // prints the total/modifier/sum line for a command
         let mut sign = String::new();
         match modifier >= 0 {
             true => sign.push('+'), // so plus sign will print on positive modifiers and zero
@@ -105,7 +151,7 @@ impl Graph {
             Goto(self.modifier_col, self.running_row),
             modifier.abs(),
             Goto(self.sum_col, self.running_row),
-            total as i16 + modifier,
+            total + modifier,
             Goto(self.equals_col, self.running_row),
             Goto(self.command_col, self.running_row + 1),
             self.divider
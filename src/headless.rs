@@ -0,0 +1,429 @@
+use crate::D;
+use crate::error::DiceError;
+use crate::hooks;
+use crate::input_handling::{Code, Command, Factor, Reroll, ThrowOptions};
+use crate::rng::RngSource;
+
+use std::fmt;
+
+use rand::{random, Rng};
+
+// rolls without the table: no animation and no terminal, just the faces and what they come to.
+// used when the input isn't coming from someone at a terminal, e.g. 'echo "2d6+3" | dice'
+
+const EXPLOSION_LIMIT: usize = 99; // most faces one die can chain together by exploding
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandResult { // how one command came out
+    pub command: String, // as it was read, e.g. "1d20+5"
+    pub label: Option<String>,
+    pub throws: Vec<Vec<(D, u16)>>, // kind and face of each die, per throw (advantage rolls are thrown more than once)
+    pub kept: Vec<Vec<bool>>, // which dice of each throw count towards its total
+    pub totals: Vec<i16>, // what each throw comes to, before the modifier
+    pub selected: usize, // the throw that counts
+    pub modifier: i16, // all the command's modifiers added together
+    pub subtotal: i16, // the selected throw's total with the modifier
+    pub pool: Option<Pool>, // for a botch or glitch pool, what its 1s did to the selected throw
+    pub fate: bool, // Fudge dice added up rather than counted, so the total can be read on the Fate ladder
+    pub rerolled: Vec<Rerolled>, // every face a die was thrown again from, in the order thrown
+}
+
+impl CommandResult {
+    pub fn new(command: &Command, throws: Vec<Vec<(D, u16)>>) -> CommandResult { // works out the totals for how a command's dice landed, and picks the throw that counts
+        let scores: Vec<Vec<i16>> = throws.iter().map(|faces| faces.iter().map(|(kind, face)| kind.score(*face)).collect()).collect();
+        let totals: Vec<i16> = throws.iter().zip(&scores)
+            .map(|(faces, scores)| {
+                if matches!(command.kind, D::PercentTens | D::PercentHundreds) {
+                    let sum: u16 = faces.iter().map(|(_, face)| face).sum();
+                    return if sum == 0 { 10i16.pow(faces.len() as u32) } else { sum as i16 }; // all zeros is 100 (or 1000)
+                }
+                command.value(scores)
+            })
+            .collect();
+        let selected = match command.code { // ties go to the first throw
+            Code::Advantage(_) | Code::Wild => (0..totals.len()).rev().max_by_key(|throw| totals[*throw]),
+            Code::Disadvantage(_) => (0..totals.len()).min_by_key(|throw| totals[*throw]),
+            _ => Some(0),
+        }.expect("Should have thrown at least once");
+        CommandResult {
+            command: command.to_string(),
+            label: command.label.clone(),
+            pool: Pool::new(command, &scores[selected]),
+            fate: command.kind == D::Fudge && command.target.is_none(),
+            kept: scores.iter().map(|scores| command.keep.kept(scores)).collect(),
+            subtotal: totals[selected] + command.modifier(),
+            modifier: command.modifier(),
+            rerolled: Vec::new(),
+            throws,
+            totals,
+            selected,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RerollReason { // why a die was picked up and thrown again
+    Rule, // the command's own reroll rule, e.g. "r1", or "ro1" for Great Weapon Fighting
+    Lock, // lock mode: it was left unlocked when the rest were thrown again
+}
+
+impl fmt::Display for RerollReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RerollReason::Rule => write!(f, "rule"),
+            RerollReason::Lock => write!(f, "lock"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rerolled { // a face a die was thrown again from, and the one it landed on next
+    pub throw: usize, // which of the command's throws the die is in
+    pub die: usize, // and where in that throw, as in CommandResult::throws
+    pub kind: D,
+    pub from: u16,
+    pub to: u16,
+    pub reason: RerollReason,
+}
+
+impl Rerolled {
+    pub fn chain(throw: usize, die: usize, kind: D, discarded: &[(u16, RerollReason)], face: u16) -> Vec<Rerolled> { // each reroll a die went through, from the faces it was thrown again from (oldest first) and the one it shows now
+        let to = discarded.iter().skip(1).map(|(face, _)| *face).chain([face]);
+        discarded.iter().zip(to).map(|((from, reason), to)| Rerolled { throw, die, kind, from: *from, to, reason: *reason }).collect()
+    }
+}
+
+impl fmt::Display for Rerolled { // e.g. "d6 1->4 (rule)"
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "d{} {}->{} ({})", self.kind, self.kind.label(self.from), self.kind.label(self.to), self.reason)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pool { // how a pool that watches its 1s came out, before any are taken off
+    pub successes: i16,
+    pub ones: i16,
+    pub dice: i16,
+    pub botch: bool, // World of Darkness: 1s cancel successes. otherwise Shadowrun: 1s can glitch
+}
+
+impl Pool {
+    pub fn new(command: &Command, scores: &[i16]) -> Option<Pool> { // successes and 1s among the scores, if the command watches its 1s
+        if !command.botch && !command.glitch {
+            return None;
+        }
+        Some(Pool { successes: command.successes(scores), ones: command.ones(scores), dice: scores.len() as i16, botch: command.botch })
+    }
+
+    pub fn botched(&self) -> bool {
+        self.botch && self.successes == 0 && self.ones > 0
+    }
+
+    pub fn glitched(&self) -> bool { // half or more of the dice came up 1
+        !self.botch && self.ones * 2 >= self.dice
+    }
+
+    pub fn banner(&self) -> Option<&'static str> { // what went wrong, if the 1s did more than cancel successes
+        match (self.botched(), self.glitched()) {
+            (true, _) => Some("botch!"),
+            (_, true) if self.successes == 0 => Some("crit glitch!"),
+            (_, true) => Some("glitch!"),
+            _ => None,
+        }
+    }
+}
+
+pub fn fate_ladder(sum: i16) -> &'static str { // the Fate ladder adjective for a sum of Fudge dice
+    match sum {
+        i16::MIN..=-2 => "Terrible",
+        -1 => "Poor",
+        0 => "Mediocre",
+        1 => "Average",
+        2 => "Fair",
+        3 => "Good",
+        4 => "Great",
+        5 => "Superb",
+        6 => "Fantastic",
+        7 => "Epic",
+        8..=i16::MAX => "Legendary",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RollResult { // a whole throw: every command's dice and totals, with the one-line summary made from them
+    pub commands: Vec<CommandResult>,
+    pub line: String, // e.g. "1d20+5: 17, 2d6+3: 9 => 26"
+    pub total: i16,
+}
+
+impl RollResult {
+    pub fn faces(&self) -> Vec<(D, u16)> { // every die's kind and face in the order thrown, explosions included
+        self.commands.iter().flat_map(|command| command.throws.iter().flatten()).copied().collect()
+    }
+
+    pub fn rerolled(&self) -> Vec<Rerolled> { // every command's rerolls, in the order thrown
+        self.commands.iter().flat_map(|command| command.rerolled.iter()).copied().collect()
+    }
+}
+
+impl fmt::Display for RollResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.line)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check { // how the sum did against a GURPS skill
+    pub made: bool,
+    critical: bool,
+    margin: i16, // skill minus sum
+}
+
+impl Check {
+    pub fn new(skill: i16, natural: i16, sum: i16) -> Check { // a natural 3-4 always succeeds and 17-18 always fails, both critically. otherwise the sum (modifier and all) has to be at or under the skill
+        let (made, critical) = match natural {
+            ..=4 => (true, true),
+            17.. => (false, true),
+            _ => (sum <= skill, false),
+        };
+        Check { made, critical, margin: skill - sum }
+    }
+}
+
+impl fmt::Display for Check { // verdict and margin, e.g. "Made +3" or "Crit miss -4"
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let verdict = match (self.critical, self.made) {
+            (true, true) => "Crit made",
+            (true, false) => "Crit miss",
+            (false, true) => "Made",
+            (false, false) => "Missed",
+        };
+        write!(f, "{verdict} {:+}", self.margin)
+    }
+}
+
+pub fn throw(command_log: &[Command], options: &ThrowOptions) -> Result<(String, i16), DiceError> { // rolls every command and returns the result line, with the total
+    let result = finish(options, roll(command_log, options)?);
+    Ok((result.line, result.total))
+}
+
+pub fn finish(options: &ThrowOptions, commands: Vec<CommandResult>) -> RollResult { // puts rolled commands together into the whole throw, and tells the hooks about it
+    let (line, total) = describe(options, &commands);
+    let result = RollResult { commands, line, total };
+    hooks::fire(&result);
+    result
+}
+
+pub fn roll(command_log: &[Command], options: &ThrowOptions) -> Result<Vec<CommandResult>, DiceError> { // rolls every command, drawing from the options' generator and seed
+    let mut rng = RngSource::new(options.rng, options.seed.unwrap_or_else(random));
+    roll_with(command_log, options, &mut rng)
+}
+
+pub fn roll_with<R: Rng + ?Sized>(command_log: &[Command], options: &ThrowOptions, rng: &mut R) -> Result<Vec<CommandResult>, DiceError> { // rolls every command, drawing from the given generator instead -- e.g. a mock one in a test. the options' seed and generator are ignored
+    if options.rerolls.is_some() || options.skill_shot || options.time_limit.is_some() || options.split.is_some() || options.horde.is_some() {
+        return Err(DiceError::NeedsTable);
+    }
+    Ok(command_log.iter().map(|command| roll_command(command, rng)).collect())
+}
+
+pub fn describe(options: &ThrowOptions, results: &[CommandResult]) -> (String, i16) { // the result line for rolled commands, with the total
+    let subtotals: Vec<i16> = results.iter().map(|result| result.subtotal).collect();
+
+    // character generation lists the ability scores, highest first
+    if options.genchar {
+        let mut scores = subtotals.clone();
+        scores.sort_unstable_by(|a, b| b.cmp(a));
+        let listed: Vec<String> = scores.iter().map(|score| format!("{score} ({:+})", (score - 10).div_euclid(2))).collect();
+        let total = scores.iter().sum::<i16>();
+        return (format!("{} (total {total})", listed.join(", ")), total);
+    }
+
+    let listed: Vec<String> = results.iter()
+        .map(|result| format!("{}: {}", result.label.as_ref().unwrap_or(&result.command), result.subtotal))
+        .collect();
+    let sum: i16 = subtotals.iter().sum();
+    let mut result = format!("{} => {sum}", listed.join(", "));
+    if let Some(skill) = options.versus {
+        let natural = results.first().map_or(sum, |check| check.totals[check.selected]); // the 3d6 before the modifier
+        result.push_str(&format!(" vs {skill} ({})", Check::new(skill as i16, natural, sum)));
+    }
+    if !results.is_empty() && results.iter().all(|result| result.fate) {
+        result.push_str(&format!(" ({})", fate_ladder(sum)));
+    }
+    let banners: Vec<&str> = results.iter().filter_map(|result| result.pool.as_ref()?.banner()).collect();
+    if !banners.is_empty() {
+        result.push_str(&format!(" ({})", banners.join(", ")));
+    }
+    (result, sum)
+}
+
+pub fn breakdown(command_log: &[Command], results: &[CommandResult]) -> Vec<String> { // every die, what's done to it, and each command's subtotal, as lines -- the graph without the positions
+    let mut lines = Vec::new();
+    for (command, rolled) in command_log.iter().zip(results) {
+        match &rolled.label {
+            Some(label) => lines.push(format!("{label} ({}):", rolled.command)),
+            None => lines.push(format!("{}:", rolled.command)),
+        }
+        for (throw, (faces, kept)) in rolled.throws.iter().zip(&rolled.kept).enumerate() {
+            let listed: Vec<String> = faces.iter().zip(kept)
+                .map(|((kind, face), kept)| {
+                    let label = match kind {
+                        D::Fudge => format!("[{}]", kind.label(*face)), // so blank faces still show up
+                        _ => kind.label(*face),
+                    };
+                    format!("d{kind} {label}{}", if *kept { "" } else { " (dropped)" })
+                })
+                .collect();
+            let taken = match rolled.throws.len() {
+                1 => String::new(),
+                _ if throw == rolled.selected => format!(" = {} (taken)", rolled.totals[throw]),
+                _ => format!(" = {}", rolled.totals[throw]),
+            };
+            lines.push(format!("  {}{taken}", listed.join(", ")));
+        }
+        for rerolled in &rolled.rerolled {
+            lines.push(format!("  rerolled {rerolled}"));
+        }
+        let mut steps = Vec::new(); // what the dice total goes through before the modifier
+        if command.factor != Factor::One {
+            steps.push(command.factor.to_string());
+        }
+        if command.clamp.is_set() {
+            steps.push(command.clamp.to_string());
+        }
+        let steps = if steps.is_empty() { String::new() } else { format!(" ({})", steps.join(", ")) };
+        let modifiers: String = command.modifiers.iter().map(|modifier| format!(" {modifier}")).collect();
+        lines.push(format!("  dice {}{steps}{modifiers} => {}", rolled.totals[rolled.selected], rolled.subtotal));
+    }
+    lines
+}
+
+fn roll_command<R: Rng + ?Sized>(command: &Command, rng: &mut R) -> CommandResult { // throws a command's dice
+    let dice = command.dice();
+    let kinds: Vec<Vec<D>> = match command.code {
+        Code::Wild => dice.into_iter().map(|kind| vec![kind]).collect(), // the trait die and the wild die
+        code => dice.chunks(dice.len() / code.throws()).map(|throw| throw.to_vec()).collect(),
+    };
+    let mut rerolled = Vec::new();
+    let mut throws: Vec<Vec<(D, u16)>> = Vec::new();
+    for (throw, kinds) in kinds.iter().enumerate() {
+        let mut faces = Vec::new();
+        for kind in kinds {
+            for (face, discarded) in roll_die(command, *kind, rng) {
+                rerolled.extend(Rerolled::chain(throw, faces.len(), *kind, &discarded, face));
+                faces.push((*kind, face));
+            }
+        }
+        throws.push(faces);
+    }
+    let mut result = CommandResult::new(command, throws);
+    result.rerolled = rerolled;
+    result
+}
+
+fn roll_die<R: Rng + ?Sized>(command: &Command, kind: D, rng: &mut R) -> Vec<(u16, Vec<(u16, RerollReason)>)> { // a die's face once any rerolls are done, followed by those of the dice it sets off by exploding -- each with the faces it was rerolled from
+    let mut faces = Vec::new();
+    loop {
+        let mut face = kind.flip(rng);
+        let mut discarded = Vec::new();
+        loop {
+            match command.reroll {
+                Reroll::Always(value) if face == value => (),
+                Reroll::Once(value) if face == value && discarded.is_empty() => (),
+                _ => break,
+            }
+            discarded.push((face, RerollReason::Rule));
+            face = kind.flip(rng);
+        }
+        faces.push((face, discarded));
+        if !command.explode || face != kind.as_number() || faces.len() >= EXPLOSION_LIMIT {
+            break;
+        }
+    }
+    faces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_handling::{generate_dice, get_options};
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn landed(input: &str, faces: &[u16]) -> RollResult { // a throw as if its dice landed on the faces, command by command
+        let (options, parsed) = get_options(input).unwrap();
+        let (_, command_log) = generate_dice(parsed, &options).unwrap();
+        let mut faces = faces.iter();
+        let commands = command_log.iter()
+            .map(|command| CommandResult::new(command, vec![command.dice().iter().map(|kind| (*kind, *faces.next().expect("a face per die"))).collect()]))
+            .collect();
+        finish(&options, commands)
+    }
+
+    #[test]
+    fn seeded_throws_repeat() {
+        let (options, parsed) = get_options("4d6kh3, 2d8+1").unwrap();
+        let (_, command_log) = generate_dice(parsed, &options).unwrap();
+        let result = |seed| finish(&options, roll_with(&command_log, &options, &mut StdRng::seed_from_u64(seed)).unwrap());
+        assert_eq!(result(3), result(3)); // dice, totals and line alike
+        assert_eq!(roll_with(&command_log, &ThrowOptions { split: Some(2), ..options }, &mut StdRng::seed_from_u64(3)).err(), Some(DiceError::NeedsTable));
+    }
+
+    #[test]
+    fn rerolls_are_kept() {
+        let chain = Rerolled::chain(1, 2, D::Six, &[(1, RerollReason::Rule), (1, RerollReason::Rule)], 4);
+        assert_eq!(chain.iter().map(|rerolled| (rerolled.from, rerolled.to)).collect::<Vec<_>>(), [(1, 1), (1, 4)]);
+        assert!(chain.iter().all(|rerolled| rerolled.throw == 1 && rerolled.die == 2));
+        assert_eq!(chain[1].to_string(), "d6 1->4 (rule)");
+
+        let (options, parsed) = get_options("30d6r1, 30d6ro1").unwrap();
+        let (_, command_log) = generate_dice(parsed, &options).unwrap();
+        let result = finish(&options, roll_with(&command_log, &options, &mut StdRng::seed_from_u64(5)).unwrap());
+        for rolled in &result.commands {
+            assert!(!rolled.rerolled.is_empty());
+            for rerolled in &rolled.rerolled {
+                assert_eq!(rerolled.from, 1);
+                assert_eq!(rerolled.reason, RerollReason::Rule);
+            }
+            let last = |die| rolled.rerolled.iter().rev().find(|rerolled| rerolled.die == die).map(|rerolled| rerolled.to);
+            for (die, (_, face)) in rolled.throws[0].iter().enumerate() {
+                assert_eq!(last(die).unwrap_or(*face), *face); // the last reroll lands on the face that counts
+            }
+        }
+        assert!(result.commands[0].throws[0].iter().all(|(_, face)| *face != 1)); // rerolled until it isn't
+        assert!(breakdown(&command_log, &result.commands).iter().any(|line| line.starts_with("  rerolled d6 1->")));
+    }
+
+    #[test]
+    fn result_lines() {
+        let result = landed("4d6kh3, 2d8+1 # bonus", &[3, 6, 2, 5, 4, 4]);
+        assert_eq!(result.line, "4d6kh3: 14, bonus: 9 => 23");
+        assert_eq!(result.total, 23);
+        assert_eq!(result.commands[0].kept[0], [true, true, false, true]);
+        assert_eq!(result.faces().len(), 6);
+        assert_eq!(landed("genchar 3d6 x2", &[1, 2, 3, 6, 6, 5]).line, "17 (+3), 6 (-2) (total 23)");
+    }
+
+    #[test]
+    fn gurps_checks() {
+        assert_eq!(landed("3d6 vs 12", &[2, 3, 4]).line, "3d6: 9 => 9 vs 12 (Made +3)");
+        assert_eq!(landed("3d6+4 vs 12", &[2, 3, 4]).line, "3d6+4: 13 => 13 vs 12 (Missed -1)");
+        assert_eq!(landed("3d6+9 vs 12", &[1, 1, 2]).line, "3d6+9: 13 => 13 vs 12 (Crit made -1)"); // the dice alone decide a critical
+        assert_eq!(landed("3d6-9 vs 12", &[6, 6, 5]).line, "3d6-9: 8 => 8 vs 12 (Crit miss +4)");
+        let (options, parsed) = get_options("3d6, d6 vs 12").unwrap();
+        assert!(generate_dice(parsed, &options).is_err()); // only a single 3d6 has natural criticals
+    }
+
+    #[test]
+    fn ladders_and_banners() {
+        assert_eq!(landed("4dF", &[3, 3, 2, 1]).line, "4dF: 1 => 1 (Average)");
+        assert_eq!(landed("4dF+1, 2dF", &[3, 3, 3, 3, 3, 3]).line, "4dF+1: 5, 2dF: 2 => 7 (Epic)");
+        assert_eq!(landed("4dF, d6", &[3, 3, 3, 3, 1]).line, "4dF: 4, 1d6: 1 => 5"); // only all-Fudge throws are on the ladder
+        assert_eq!(landed("2d10>=10 botch", &[1, 4]).line, "2d10>=10 botch: 0 => 0 (botch!)");
+        assert_eq!(landed("3d10>=8 botch", &[1, 9, 9]).line, "3d10>=8 botch: 1 => 1");
+        assert_eq!(landed("sr 2d6", &[1, 5]).line, "sr 2d6: 1 => 1 (glitch!)");
+        assert_eq!(landed("sr 2d6", &[1, 1]).line, "sr 2d6: 0 => 0 (crit glitch!)");
+        assert_eq!(landed("sr 3d6", &[1, 5, 6]).line, "sr 3d6: 2 => 2");
+    }
+}
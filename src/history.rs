@@ -0,0 +1,270 @@
+use crate::D;
+use crate::headless::Rerolled;
+use crate::input_handling::{self, Code, Factor, Keep, Reroll};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// a record of every throw, kept in a file one line each: timestamp, command, faces, total, seed, each die's kind and face, where it was thrown, and any rerolls, tab-separated.
+// the faces are a JSON list, as a Fudge die's blank is a space. older lines have them space-separated
+
+pub struct Entry { // one throw
+    pub time: u64, // seconds since the Unix epoch
+    pub command: String, // as typed
+    pub faces: Vec<String>, // every die in the order thrown, explosions included
+    pub dice: Vec<(String, u16)>, // the same dice as kind ("20", "F") and face, for working with the numbers
+    pub total: i16,
+    pub seed: Option<u64>, // what the dice were thrown with, for replaying. lines from before seeds were kept have none
+    pub table: Option<bool>, // whether the dice tumbled across the table (which lands different faces from the same seed) or were thrown without it. older lines don't say
+    pub rerolled: Vec<String>, // each face a die was thrown again from, with what replaced it and why, e.g. "d6 1->4 (rule)"
+}
+
+impl Entry {
+    pub fn new(command: &str, dice: &[(D, u16)], rerolled: &[Rerolled], total: i16, seed: u64, table: bool) -> Entry { // a throw made just now
+        Entry {
+            time: now(),
+            command: command.trim().replace('\t', " "),
+            faces: dice.iter().map(|(kind, face)| kind.label(*face)).collect(),
+            dice: dice.iter().map(|(kind, face)| (kind.to_string(), *face)).collect(),
+            total,
+            seed: Some(seed),
+            table: Some(table),
+            rerolled: rerolled.iter().map(|rerolled| rerolled.to_string()).collect(),
+        }
+    }
+
+    fn parse(line: &str) -> Option<Entry> { // reads a line of the history file back
+        let mut fields = line.split('\t');
+        let time = fields.next()?.parse::<u64>().ok()?;
+        let command = fields.next()?.to_string();
+        let faces = match fields.next()? {
+            faces if faces.starts_with('[') => serde_json::from_str(faces).ok()?,
+            faces => faces.split_whitespace().map(String::from).collect(),
+        };
+        let total = fields.next()?.parse::<i16>().ok()?;
+        let seed = fields.next().and_then(|seed| seed.parse::<u64>().ok());
+        let dice = fields.next().unwrap_or_default().split_whitespace()
+            .filter_map(|die| die.split_once(':').and_then(|(kind, face)| Some((kind.to_string(), face.parse::<u16>().ok()?))))
+            .collect();
+        let table = match fields.next() {
+            Some("table") => Some(true),
+            Some("plain") => Some(false),
+            _ => None,
+        };
+        let rerolled = fields.next().unwrap_or_default().split(',').filter(|rerolled| !rerolled.is_empty()).map(String::from).collect();
+        Some(Entry { time, command, faces, dice, total, seed, table, rerolled })
+    }
+
+    fn line(&self) -> String { // how the throw is kept in the history file, as parse() reads it back
+        let seed = self.seed.map(|seed| seed.to_string()).unwrap_or_default();
+        let dice: Vec<String> = self.dice.iter().map(|(kind, face)| format!("{kind}:{face}")).collect();
+        let table = match self.table {
+            Some(true) => "table",
+            Some(false) => "plain",
+            None => "",
+        };
+        let faces = serde_json::to_string(&self.faces).unwrap_or_default();
+        format!("{}\t{}\t{faces}\t{}\t{seed}\t{}\t{table}\t{}", self.time, self.command, self.total, dice.join(" "), self.rerolled.join(","))
+    }
+}
+
+impl fmt::Display for Entry { // e.g. "adv d20+5  [4 17] = 22"
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}  [{}] = {}", self.command, self.faces.join(" "), self.total)
+    }
+}
+
+pub fn record(path: &Path, entry: &Entry) -> Result<(), &'static str> { // adds a throw to the end of the history file
+    let line = format!("{}\n", entry.line());
+    OpenOptions::new().create(true).append(true).open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .map_err(|_| "Could not write to the history file")
+}
+
+pub fn recent(path: &Path, count: usize) -> Vec<Entry> { // the last few throws, oldest first. lines that don't read don't count towards them
+    let contents = fs::read_to_string(path).unwrap_or_default();
+    let mut entries: Vec<Entry> = contents.lines().filter_map(Entry::parse).collect();
+    entries.drain(..entries.len().saturating_sub(count));
+    entries
+}
+
+pub fn export(path: &Path, entries: &[Entry]) -> Result<(), &'static str> { // writes throws out as plain text, one numbered line each with the time since the first
+    let start = entries.first().map(|entry| entry.time).unwrap_or_default();
+    let mut text = format!("{} rolls\n", entries.len());
+    for (n, entry) in entries.iter().enumerate() {
+        let elapsed = entry.time.saturating_sub(start);
+        let rerolled = if entry.rerolled.is_empty() { String::new() } else { format!("  rerolled {}", entry.rerolled.join(", ")) };
+        text.push_str(&format!("{:>3}. +{:02}:{:02}  {}  faces {}  total {}{rerolled}\n", n + 1, elapsed / 60, elapsed % 60, entry.command, entry.faces.join(" "), entry.total));
+    }
+    fs::write(path, text).map_err(|_| "Could not write the export file")
+}
+
+pub fn usage(entries: &[Entry]) -> Vec<String> { // how the tool gets used, worked out from the history alone: the most rolled expressions and how often each feature comes up
+    const MOST_ROLLED: usize = 5;
+    let Some(first) = entries.first() else { return vec![String::from("No rolls yet")] };
+    let mut rolled: HashMap<String, usize> = HashMap::new();
+    let mut used: HashMap<&'static str, usize> = HashMap::new();
+    for entry in entries {
+        *rolled.entry(entry.command.to_lowercase()).or_default() += 1;
+        for feature in features(&entry.command) {
+            *used.entry(feature).or_default() += 1;
+        }
+    }
+    let mut rolled: Vec<(String, usize)> = rolled.into_iter().collect();
+    rolled.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let mut used: Vec<(&str, usize)> = used.into_iter().collect();
+    used.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+    let mut lines = vec![format!("{} rolls, the first {}", entries.len(), ago(first.time)), String::from("Most rolled:")];
+    lines.extend(rolled.iter().take(MOST_ROLLED).map(|(command, count)| format!("{count:>6}  {command}")));
+    if !used.is_empty() {
+        lines.push(String::from("Features:"));
+        lines.extend(used.iter().map(|(feature, count)| format!("{count:>6}  {feature}")));
+    }
+    let streaks = streaks(entries);
+    if !streaks.is_empty() {
+        lines.push(String::from("Recent faces:"));
+        lines.extend(streaks);
+    }
+    lines
+}
+
+fn streaks(entries: &[Entry]) -> Vec<String> { // a sparkline of the last few faces of each kind of numbered die, oldest first, to settle arguments about hot and cold dice
+    const STREAK_LENGTH: usize = 40;
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let mut faces: HashMap<u16, Vec<u16>> = HashMap::new();
+    for (kind, face) in entries.iter().flat_map(|entry| entry.dice.iter()) {
+        match kind.parse::<u16>() {
+            Ok(sides) if sides != 100 && sides != 1000 && (1..=sides).contains(face) => faces.entry(sides).or_default().push(*face), // percentile dice show tens and hundreds, so they're left out
+            _ => (),
+        }
+    }
+    let mut kinds: Vec<(u16, Vec<u16>)> = faces.into_iter().collect();
+    kinds.sort_unstable_by_key(|(sides, _)| *sides);
+    kinds.into_iter()
+        .map(|(sides, faces)| {
+            let recent = &faces[faces.len().saturating_sub(STREAK_LENGTH)..];
+            let top = (sides as usize - 1).max(1); // 1 is the lowest bar and the top face the highest
+            let line: String = recent.iter().map(|face| BARS[((face - 1) as usize * (BARS.len() - 1) + top / 2) / top]).collect();
+            let average = recent.iter().map(|face| *face as f64).sum::<f64>() / recent.len() as f64;
+            format!("{:>6}  {line}  average {average:.1} (expected {:.1})", format!("d{sides}"), (sides as f64 + 1.0) / 2.0)
+        })
+        .collect()
+}
+
+fn features(command: &str) -> Vec<&'static str> { // what a throw made use of beyond plain dice and modifiers, read back by parsing it again
+    let Ok((options, parsed)) = input_handling::get_options(command) else { return Vec::new() };
+    let Ok((_, log)) = input_handling::generate_dice(parsed, &options) else { return Vec::new() };
+    let mut features = Vec::new();
+    let throw_wide = [
+        (options.time_limit.is_some(), "timed"),
+        (options.rerolls.is_some(), "lock"),
+        (options.skill_shot, "skill"),
+        (options.genchar, "genchar"),
+        (options.versus.is_some(), "vs"),
+        (options.split.is_some(), "split"),
+        (options.burst, "burst"),
+        (options.horde.is_some(), "horde"),
+    ];
+    features.extend(throw_wide.iter().filter(|(on, _)| *on).map(|(_, feature)| *feature));
+    let per_command = [
+        (log.iter().any(|command| matches!(command.code, Code::Advantage(_))), "advantage"),
+        (log.iter().any(|command| matches!(command.code, Code::Disadvantage(_))), "disadvantage"),
+        (log.iter().any(|command| command.code == Code::Percentile), "percentile"),
+        (log.iter().any(|command| command.code == Code::Wild), "wild die"),
+        (log.iter().any(|command| command.keep != Keep::All), "keep/drop"),
+        (log.iter().any(|command| command.explode), "exploding"),
+        (log.iter().any(|command| command.reroll != Reroll::Never), "reroll"),
+        (log.iter().any(|command| command.target.is_some()), "pool"),
+        (log.iter().any(|command| command.factor != Factor::One || command.clamp.is_set()), "multiply/clamp"),
+        (log.iter().any(|command| command.label.is_some()), "label"),
+    ];
+    features.extend(per_command.iter().filter(|(on, _)| *on).map(|(_, feature)| *feature));
+    features
+}
+
+pub fn now() -> u64 { // seconds since the Unix epoch
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default()
+}
+
+pub fn ago(time: u64) -> String { // how long before now a throw was, roughly, e.g. "5m ago"
+    match now().saturating_sub(time) {
+        0..=59 => String::from("just now"),
+        seconds @ 60..=3599 => format!("{}m ago", seconds / 60),
+        seconds @ 3600..=86399 => format!("{}h ago", seconds / 3600),
+        seconds => format!("{}d ago", seconds / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::RerollReason;
+
+    fn read_back(entry: &Entry) -> Entry {
+        Entry::parse(&entry.line()).expect("a recorded line should read back")
+    }
+
+    #[test]
+    fn entry_round_trip() {
+        let entry = Entry::new("adv d20+5\t# hit", &[(D::Twenty, 4), (D::Twenty, 17)], &[], 22, 42, true);
+        let read = read_back(&entry);
+        assert_eq!(read.time, entry.time);
+        assert_eq!(read.command, "adv d20+5 # hit"); // tabs would split the line
+        assert_eq!(read.faces, ["4", "17"]);
+        assert_eq!(read.dice, [(String::from("20"), 4), (String::from("20"), 17)]);
+        assert_eq!(read.total, 22);
+        assert_eq!(read.seed, Some(42));
+        assert_eq!(read.table, Some(true));
+        assert_eq!(read.to_string(), "adv d20+5 # hit  [4 17] = 22");
+        assert!(read.rerolled.is_empty());
+    }
+
+    #[test]
+    fn blank_faces_round_trip() {
+        let entry = Entry::new("4dF", &[(D::Fudge, 2), (D::Fudge, 3), (D::Fudge, 2), (D::Fudge, 1)], &[], 0, 3, false);
+        let read = read_back(&entry);
+        assert_eq!(read.faces, [" ", "+", " ", "-"]);
+        assert_eq!(read.dice.len(), 4);
+        assert_eq!(read.to_string(), "4dF  [  +   -] = 0");
+    }
+
+    #[test]
+    fn rerolls_round_trip() {
+        let rerolled = [
+            Rerolled { throw: 0, die: 0, kind: D::Six, from: 1, to: 2, reason: RerollReason::Rule },
+            Rerolled { throw: 0, die: 0, kind: D::Six, from: 2, to: 5, reason: RerollReason::Lock },
+        ];
+        let entry = Entry::new("lock 1 2d6r1", &[(D::Six, 5), (D::Six, 3)], &rerolled, 8, 7, true);
+        assert_eq!(read_back(&entry).rerolled, ["d6 1->2 (rule)", "d6 2->5 (lock)"]);
+        let path = std::env::temp_dir().join(format!("dice_export_test_{}", std::process::id()));
+        export(&path, &[entry]).unwrap();
+        let exported = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).ok();
+        assert!(exported.contains("faces 5 3  total 8  rerolled d6 1->2 (rule), d6 2->5 (lock)"), "{exported}");
+    }
+
+    #[test]
+    fn older_lines() {
+        let read = Entry::parse("1700000000\t2d6\t3 4\t7").expect("lines from before seeds were kept should still read");
+        assert_eq!(read.seed, None);
+        assert!(read.dice.is_empty());
+        assert_eq!(read.table, None);
+        assert!(Entry::parse("not a roll").is_none());
+        assert!(Entry::parse("1700000000\t2d6\t3 4\tseven").is_none());
+    }
+
+    #[test]
+    fn recent_skips_unreadable_lines() {
+        let path = std::env::temp_dir().join(format!("dice_history_test_{}", std::process::id()));
+        let lines = ["1\td4\t1\t1", "garbage", "2\td4\t2\t2", "3\td4\t3\t3", "more garbage"];
+        fs::write(&path, lines.join("\n")).unwrap();
+        let totals: Vec<i16> = recent(&path, 2).iter().map(|entry| entry.total).collect();
+        fs::remove_file(&path).ok();
+        assert_eq!(totals, [2, 3]);
+    }
+}
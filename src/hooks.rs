@@ -0,0 +1,27 @@
+use crate::headless::RollResult;
+
+use std::sync::{Arc, Mutex};
+
+// callbacks told about every throw once it's finished, for programs using this as a library, e.g.
+//     dice::hooks::register(|result| println!("rolled {}", result.total));
+// to post to a webhook, play a sound, or keep a log. they're called in the order registered,
+// on the thread that made the throw, by both the table and the headless throws
+
+type Hook = Arc<dyn Fn(&RollResult) + Send + Sync>;
+
+static HOOKS: Mutex<Vec<Hook>> = Mutex::new(Vec::new());
+
+pub fn register(hook: impl Fn(&RollResult) + Send + Sync + 'static) {
+    HOOKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push(Arc::new(hook));
+}
+
+pub fn clear() { // forgets every hook registered so far
+    HOOKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+}
+
+pub(crate) fn fire(result: &RollResult) {
+    let hooks = HOOKS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone(); // let go of the list before calling them, so a hook can roll (or register) without waiting on itself
+    for hook in hooks {
+        hook(result);
+    }
+}
@@ -1,22 +1,42 @@
 mod die;
+pub mod builder;
+pub mod error;
+#[cfg(feature = "tui")]
+pub mod events;
+#[cfg(feature = "tui")]
 mod table;
 mod util;
 mod directions;
+#[cfg(feature = "tui")]
 mod graph;
+pub mod headless;
+pub mod history;
+pub mod hooks;
+pub mod narration;
+pub mod rng;
+pub mod rpc;
+pub mod svg;
+pub mod watch;
+pub mod weighted;
 
-use crate::die::{Die, D};
-use crate::input_handling::Code;
-use crate::table::Table;
-use crate::util::*;
+pub use crate::die::{D, Die, RollEvent};
+use crate::error::DiceError;
+use crate::headless::RollResult;
+use crate::rng::{Backend, RngSource};
 
-use std::sync::mpsc::channel;
-use std::io::{stdin, Write};
-use std::time::Duration;
-use std::thread;
+use std::{sync::mpsc::channel, thread};
 
-use termion::event::Key;
-use termion::cursor::Goto; // Goto: (col, row)
-use termion::input::TermRead;
+use rand::Rng;
+
+// the table, the animation, and the prompt's line editing need the terminal, so they're only built with the 'tui' feature
+#[cfg(feature = "tui")]
+use crate::{events::{Event, Events}, input_handling::{Command, ThrowOptions}, narration::Narration, table::Table, util::*};
+#[cfg(feature = "tui")]
+use std::{io::{stdout, Write}, sync::mpsc::Sender, time::Duration};
+#[cfg(feature = "tui")]
+use termion::{cursor::Goto, event::Key, raw::IntoRawMode, terminal_size}; // Goto: (col, row)
+#[cfg(feature = "tui")]
+use rand::random;
 
 // functions that initiate dice rolling or process user input
 
@@ -25,46 +45,50 @@ use termion::input::TermRead;
     This program interprets 0 on that die as representing 0, and as such:
         0 + 10 = 10
         0 + 00 = 100
+    d1000 follows suit with a hundreds die, so 000 + 00 + 0 = 1000.
 */
 
-pub fn throw(code: Code, dice: Vec<D>, modifiers: Vec<i16>, command_log: Vec<String>) -> Option<String> { // most of the program
+#[cfg(feature = "tui")]
+pub struct Thrown { // how a throw at the table came out
+    pub result: RollResult,
+    pub working: String, // the result line as the prompt shows it: each die (coloured) and the sum worked out
+    pub exit: bool, // left with Esc, to close the program
+}
+
+#[cfg(feature = "tui")]
+pub fn throw(dice: Vec<D>, command_log: Vec<Command>, mut options: ThrowOptions, narration: &Narration, events: &mut Events) -> Thrown { // most of the program. returns how the throw came out
     
     // setup
-    let mut table = Table::new(code, modifiers, command_log);
-    let (tx, rx) = channel();
+    let still = options.no_anim;
+    options.seed = options.seed.or_else(|| Some(random()));
+    let mut table = Table::new(command_log, options, narration.clone());
     table.hide_cursor();
 
-    // display pending throw
-    table.print_throw();
-    table.clear_screen();
-    thread::sleep(Duration::from_millis(200));
-
-    // throw each die on its own thread
-    for (id, kind) in dice.iter().enumerate() { // ids will start at zero
-        let tx_copy = tx.clone();
-        let k = kind.clone(); // not sure this is efficient but ah well
-        table.log_kind(id, kind.clone());
-        thread::spawn(move || {
-            let mut die = Die::new(id, k, tx_copy);
-            die.roll();
-        });
+    // display pending throw (without the animation, only when there's a countdown or minigame to show)
+    if !still || options.skill_shot || options.time_limit.is_some() {
+        table.print_throw(events);
+        table.clear_screen();
+        thread::sleep(Duration::from_millis(200));
     }
 
-    // receive rolling
-    drop(tx);
-    for (id, face, position) in rx {
-        table.update(id, face, position); // displays and logs positions/faces up
+    // throw each die on its own thread
+    let dice: Vec<(usize, D)> = dice.into_iter().enumerate().collect(); // ids will start at zero
+    for (id, kind) in dice.iter() {
+        table.log_kind(*id, *kind);
     }
+    roll_dice(&mut table, dice, still, events);
 
     table.redraw(); // in case dice on screen have been "erased" (caused by update() and dice overlapping, or a die running over another stationary one)
     table.crit_colour();
 
     // pause
-    let msg = " PRESS ANY KEY ";
-    let (_, row) = terminal_centre();
-    write!(table.surface, "{}{msg}", Goto(centre(msg), row)).unwrap();
-    table.surface.flush().unwrap();
-    press_to_continue();
+    if !still {
+        let msg = " PRESS ANY KEY ";
+        let (_, row) = terminal_centre();
+        write!(table.surface, "{}{msg}", Goto(centre(msg), row)).unwrap();
+        table.surface.flush().unwrap();
+        events.key();
+    }
 
     // display results
     if let Err(error) = table.show_math() {
@@ -72,13 +96,10 @@ pub fn throw(code: Code, dice: Vec<D>, modifiers: Vec<i16>, command_log: Vec<Str
     }
 
     // allow display toggle before exiting
-    let input = stdin();
-    for key in input.keys() {
-        match key.unwrap() {
-            Key::Esc => {
-                table.show_cursor();
-                return None; // exit
-            },
+    let exit = loop {
+        let Some(key) = events.key() else { break true }; // stdin has ended, so there's nothing more to do
+        match key {
+            Key::Esc => break true, // leave the program
             Key::Char('t') => { // toggle between math and table
                 match table.graph_on {
                     true => table.redraw(),
@@ -93,21 +114,170 @@ pub fn throw(code: Code, dice: Vec<D>, modifiers: Vec<i16>, command_log: Vec<Str
                     }
                 }
             }
-            Key::Char('r') => { // return to command line
-                table.show_cursor();
-                return Some(table.do_math()); // return Some() to signal the user wants to reroll on returning
+            Key::Char(c @ '1'..='9') if table.can_lock() => { // lock or unlock a die before the next reroll
+                table.toggle_lock(c as usize - '1' as usize);
+                if table.graph_on {
+                    if let Err(error) = table.show_math() {
+                        table.print_error(error);
+                    }
+                }
+            },
+            Key::Char(c @ '1'..='9') if table.can_split() => { // mark a target as having saved for half, or not
+                table.toggle_save(c as u16 - '0' as u16);
+                if table.graph_on {
+                    if let Err(error) = table.show_math() {
+                        table.print_error(error);
+                    }
+                }
+            },
+            Key::Char('e') if table.rerolls_left() > 0 => { // throw the unlocked dice again
+                table.redraw();
+                let dice = table.take_reroll();
+                roll_dice(&mut table, dice, still, events);
+                table.redraw();
+                thread::sleep(Duration::from_millis(500)); // let the new faces register before the graph covers them
+                if let Err(error) = table.show_math() {
+                    table.print_error(error);
+                }
             },
+            Key::Char('r') => break false, // return to command line
             _ => (),
         }
+    };
+
+    // however it was left, the throw counts
+    table.show_cursor();
+    let result = table.result();
+    hooks::fire(&result);
+    Thrown { working: table.do_math(), result, exit }
+}
+
+pub fn roll(input: &str) -> Result<RollResult, DiceError> { // parses and throws a line of input without the table or terminal, for use from other programs, e.g. roll("adv d20+5")
+    let (options, parsed) = input_handling::get_options(input)?;
+    let (_, command_log) = input_handling::generate_dice(parsed, &options)?;
+    let commands = headless::roll(&command_log, &options)?;
+    Ok(headless::finish(&options, commands))
+}
+
+pub fn roll_with<R: Rng + ?Sized>(input: &str, rng: &mut R) -> Result<RollResult, DiceError> { // like roll(), drawing from the given generator -- e.g. a seeded or mock one in a test. a '--seed' in the input is ignored
+    let (options, parsed) = input_handling::get_options(input)?;
+    let (_, command_log) = input_handling::generate_dice(parsed, &options)?;
+    let commands = headless::roll_with(&command_log, &options, rng)?;
+    Ok(headless::finish(&options, commands))
+}
+
+#[cfg(feature = "tui")]
+fn roll_dice(table: &mut Table, dice: Vec<(usize, D)>, still: bool, events: &mut Events) { // throws dice onto the table (or sets them down, if still) and waits for all of them to settle
+    let (tx, rx) = channel();
+    let forward = events.sender();
+    thread::spawn(move || { // passes what the dice do along to the events, where keys pressed meanwhile are kept for later
+        for event in rx {
+            if forward.send(Event::Die(event)).is_err() {
+                break;
+            }
+        }
+    });
+    let mut rolling = dice.len(); // dice still moving
+    for (id, kind) in dice {
+        roll_die(id, kind, tx.clone(), still, table.die_rng(id));
+    }
+
+    // receive rolling
+    while rolling > 0 {
+        let event = events.die();
+        let (id, face, position) = event.die();
+        table.update(id, face, position); // displays and logs positions/faces up
+        let RollEvent::Settled { .. } = event else { continue };
+        rolling -= 1;
+        if table.rerolls(id, face) { // pick the die back up and throw it again
+            let kind = table.log_reroll(id);
+            roll_die(id, kind, tx.clone(), still, table.die_rng(id));
+            rolling += 1;
+        } else if table.explodes(id, face) { // throw another die of the same kind onto the table
+            let next_id = table.next_id();
+            let kind = table.log_explosion(id, next_id);
+            roll_die(next_id, kind, tx.clone(), still, table.die_rng(next_id));
+            rolling += 1;
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+fn roll_die(id: usize, kind: D, tx: Sender<RollEvent>, still: bool, rng: RngSource) { // spawns a die and rolls it on its own thread, or sets it straight down
+    let area = terminal_size().unwrap();
+    if still {
+        Die::new(id, kind, tx, rng, area).settle();
+        return;
     }
+    thread::spawn(move || {
+        let mut die = Die::new(id, kind, tx, rng, area);
+        die.roll();
+    });
+}
 
-    None // returns None if you want program to close upon returning
+pub fn tumble(dice: &[D], area: (u16, u16), seed: u64, backend: Backend) -> Result<impl Iterator<Item = RollEvent>, DiceError> { // throws dice around an area of (cols, rows) on their own threads, the way the table does, and streams what each one does -- for other frontends (a GUI, the web) to animate. ends once they've all settled
+    const MIN_AREA: (u16, u16) = (12, 6); // room for the widest face to bounce
+    if dice.contains(&D::Custom(0)) { // checked before any are thrown, as it would panic on its own thread
+        return Err(DiceError::NoSides);
+    }
+    let area = (area.0.max(MIN_AREA.0), area.1.max(MIN_AREA.1));
+    let (tx, rx) = channel();
+    for (id, kind) in dice.iter().enumerate() {
+        let rng = RngSource::new(backend, rng::mix(seed.wrapping_add(id as u64))); // seeded like the table's dice, so the same seed lands the same faces (before any rerolls or explosions)
+        let mut die = Die::new(id, *kind, tx.clone(), rng, area);
+        thread::spawn(move || die.roll());
+    }
+    Ok(rx.into_iter())
 }
 
-pub fn get_input() -> String {
-    let mut input_line = String::new();
-    stdin().read_line(&mut input_line).expect("failed to read input");
-    input_line
+#[cfg(feature = "tui")]
+pub fn get_completed_input(prompt: &str, words: &[String], recent: &[String], events: &mut Events) -> String { // reads a line key by key so Tab can complete the word being typed from the given words (or the whole line from a recent one)
+    let mut screen = match stdout().into_raw_mode() {
+        Ok(screen) => screen,
+        Err(_) => return events.line(), // not a terminal, so there's nothing to complete on
+    };
+    let mut line = String::new();
+    while let Some(key) = events.key() {
+        match key {
+            Key::Char('\n') => break,
+            Key::Char('\t') => {
+                let start = line.rfind([' ', ',']).map_or(0, |i| i + 1);
+                let word = line[start..].to_lowercase();
+                let mut options: Vec<&String> = words.iter().filter(|option| option.starts_with(&word)).collect();
+                if start == 0 { // a recent roll can only stand in for the whole line
+                    options.extend(recent.iter().filter(|option| option.to_lowercase().starts_with(&word)));
+                }
+                let Some(first) = options.first() else { continue };
+                let shared = options.iter().fold(first.len(), |shared, option| { // how much all the options agree on
+                    first.chars().zip(option.chars()).take(shared).take_while(|(a, b)| a.eq_ignore_ascii_case(b)).count()
+                });
+                if shared > word.len() {
+                    let completion: String = first.chars().skip(word.chars().count()).take(shared - word.len()).collect();
+                    line.push_str(&completion);
+                    write!(screen, "{completion}").unwrap();
+                } else if options.len() > 1 { // nothing more to fill in, so list what it could be and ask again
+                    let listed: Vec<&str> = options.iter().map(|option| option.as_str()).collect();
+                    write!(screen, "\r\n{}\r\n{prompt}{line}", listed.join("  ")).unwrap();
+                }
+            },
+            Key::Backspace if !line.is_empty() => {
+                line.pop();
+                write!(screen, "\x08 \x08").unwrap();
+            },
+            Key::Ctrl('c') | Key::Ctrl('d') => { // raw mode swallows the signal, so treat it as leaving
+                line = String::from("quit");
+                break;
+            },
+            Key::Char(c) => {
+                line.push(c);
+                write!(screen, "{c}").unwrap();
+            },
+            _ => (),
+        }
+        screen.flush().unwrap();
+    }
+    write!(screen, "\r\n").unwrap();
+    line
 }
 
 pub fn help() {
@@ -115,121 +285,977 @@ pub fn help() {
 Enter dice rolls in the format:
 '[coefficient]d[die kind]+/-[modifier]'.
 Separate roll commands with commas or slashes.
+Label a command with '#' or quotes (up to 24 characters):
+'2d6+3 # fire damage', 'd20+5 \"attack\"'.
+Name modifiers to see them listed separately:
+'d20 +3(str) +2(prof) -1(cover)'.
 
 Special rolls --
-Advantage roll: 'adv d[dice kind]'.
-Disadvantage roll: 'disadv d[dice kind]'.
-Percentile roll: 'd100' or 'd%'.
+Advantage roll: 'adv d20', or any roll like 'adv 2d6'
+(throws it twice and takes the better total).
+Best of three: 'adv3 d20' (e.g. Elven Accuracy).
+Disadvantage roll: 'disadv d20' (takes the worse).
+Percentile roll: 'd100' or 'd%' ('d1000' adds a
+hundreds die).
+Dice may have any number of sides up to 99 (e.g. 'd3', '2d30').
+Fudge/FATE dice: '4dF' (each counts -1, 0, or +1; totals are named on the Fate ladder).
+Exploding dice: 'd6!' rolls another die
+whenever one lands on its highest face.
+Reroll a face: '2d6r1' (until it stops showing)
+or '2d6ro1' (only once).
+Flip a coin with 'coin' or 'flip' ('5 coins' for more).
+Heads count 1 and tails 0.
+Success pools: '8d10>=7' counts the dice that
+reach the target instead of adding them up.
+Add 'botch' ('5d10>=6 botch') and each 1 cancels
+a success -- 1s with no successes are a botch.
+Shadowrun: 'sr 12d6' counts 5s and 6s as hits and
+warns of a glitch when half the dice show 1.
+Keep or drop dice after the die kind:
+'4d6kh3' (keep highest 3), '4d6dl1' (drop lowest 1),
+also 'kl' (keep lowest) and 'dh' (drop highest).
+Keep the middle die: 'mid 3d20'.
+Savage Worlds: 'wild d8+1' throws the trait die and
+a wild d6, both acing, and keeps the higher.
+
+Timed roll: 'timed 10s d20' rolls by itself
+if no key is pressed before the countdown ends.
+Lock mode: 'lock 5d6' (or 'lock 3 5d6' for three rerolls)
+lets you lock dice with the number keys after
+the roll and throw the rest again with 'e'.
+Skill shot: 'skill d20+3' -- press a key as the marker
+crosses the centre for +1 (or -1 if you miss badly).
+Ability scores: 'genchar' rolls 4d6kh3 six times and
+lists them sorted with their modifiers. Give your
+own method and count with 'genchar 3d6 x7'.
+Check bought scores with 'pointbuy 15,14,13,12,10,8'
+(costs and the 27-point budget).
+
+Multiply or divide a roll's dice before the
+modifier: '2d6*10', '8d6/2' (rounds down).
+Clamp the dice total: '3d6min8', 'd20max15'.
+Subtract dice with a minus: '2d8-1d4'.
+Repeat a roll with its own total each time:
+'3x(2d6+1)'.
+Area damage: 'split 8d6 among 4' rolls once for
+every target -- press a target's number after the
+roll to mark a save for half.
+GURPS checks: '3d6 vs 14' rolls under the skill and
+shows the margin (the dice alone showing 3-4 or
+17-18 are criticals, whatever the modifier).
+
+Flags on the end change a single roll: '2d6+3 --fast'
+skips the animation, '--blind' keeps the result hidden
+(it's still in the history), '--seed 42' throws from a
+set seed, and '--label \"trap dmg\"' labels it.
 
 Modifiers may be applied to any roll type,
-but you may not add additional dice
-to a special roll.
+and special rolls can share a line with
+other commands: 'adv d20+5, 2d6+3'.
+
+Record a macro with 'record macro attack', make your
+rolls, then 'stop'. Enter 'attack' to roll them again.
+Name a single roll with 'alias attack = d20+7' ('alias'
+lists them). Macros and aliases are kept in ~/.dice_macros.
+Press Tab at the prompt to complete keywords, macros,
+aliases, and recent rolls.
+'?name' in a roll asks for a value each time, e.g.
+'d20+?bonus' or '3d6 vs ?skill'.
+
+Roll many expressions at once, each in its own colour,
+with 'burst d20+4, d20+4, 2d6' -- or 'burst attacks.txt'
+for one expression per line of a file.
+Many identical attackers: 'horde 8 atk d20+4 ac 15 dmg 1d6+2'
+lists each attack with its damage, totals the hits
+(a natural 20 crits for double damage dice, a 1 misses).
+
+Rolls can be piped in too, one per line, for plain
+results without the table: echo \"2d6+3\" | dice
+-- or listed in a file: dice --file encounter.txt
+-- or given as arguments for a single roll: dice 2d6+3
+('--quiet' prints only each roll's number, '--verbose'
+every die and step, '--check 15' exits 0 if the total
+meets the DC and 1 if not -- a bad roll or flag exits 2).
+Skip the rolling animation with 'dice --no-anim' (or
+'no_anim = true' in ~/.dice_config). Keep a spreadsheet
+of every throw with 'dice --csv rolls.csv' (or
+'csv = rolls.csv' in the config), or draw the latest
+throw's results as a picture with 'dice --svg roll.svg',
+for wikis and recaps. Lines like 'narrate crit = A
+devastating blow!' or 'narrate 15-19 = A solid hit on
+{label}' in the config are printed under the results, and
+a line like 'weighted loot = 1, 1, 2, 6' makes a die whose
+faces come up at those odds, thrown as '2dloot'. 'dice --seed 42' makes
+every throw come out the same each time it's run, for demos
+and tests. Pick the generator with '--rng fast' (xoshiro) or
+'--rng os' (the system's secure one, which can't be seeded
+or replayed), or 'rng = fast' in the config.
+Editor plugins can check and roll expressions over
+JSON-RPC on stdin/stdout with 'dice --rpc'.
+'dice watch notes.md' rolls each '[[d20+5]]' in a file
+as it's saved and writes the result in a comment after it
+(set 'watch_open' and 'watch_close' in the config for
+other delimiters).
+New to this? 'dice tutorial' walks through the basics.
+Keep separate games apart with 'dice --profile campaign'
+(or 'profile campaign' at the prompt) -- each profile has
+its own config and macros under ~/.dice_profiles.
+With TERM=dumb (or 'plain = true' in the config), rolls
+print as plain lines without the table, for screen readers
+and minimal terminals.
+('#' or '//' starts a comment line).
+
+Every throw is kept in ~/.dice_history -- enter 'history'
+to see the last 10, or 'history 30' for more. 'replay 3'
+throws the third from last again, with the same faces and
+bounces (in a window the same size). Save this session's
+rolls to a text file with 'export session.txt', or see
+what gets rolled most (and which aliases never are), with
+a line of each die's recent faces, with 'usage' -- it only
+reads the history file. Enter 'sandbox' to practise or
+demonstrate without any of it being kept (and again to go
+back).
+
+For the superstitious: 'die lucky = d20' makes a die of your
+own, with its own seed and lifetime stats ('die' lists them).
+Throw it with 'roll with lucky+5', and retire it with
+'bench lucky' when it's rolled one too many 1s ('unbench
+lucky' brings it back). They're kept in ~/.dice_named_dice.
 
 Enter 'quit' or 'exit' to close program.";
 
     println!("{help}");
 }
 
+pub fn point_buy(input: &str) -> Result<String, &'static str> { // checks a set of bought ability scores ("15,14,13,12,10,8") against the standard 27-point buy
+    const COSTS: [(i16, u16); 8] = [(8, 0), (9, 1), (10, 2), (11, 3), (12, 4), (13, 5), (14, 7), (15, 9)];
+    const BUDGET: u16 = 27;
+    const SCORES: usize = 6;
+    let mut listed = Vec::new();
+    let mut spent = 0;
+    for score in input.split([',', ' ']).filter(|score| !score.is_empty()) {
+        let score = score.parse::<i16>().map_err(|_| "Point buy scores must be numbers")?;
+        let cost = match COSTS.iter().find(|(value, _)| *value == score) {
+            Some((_, cost)) => *cost,
+            None => return Err("Point buy scores must be between 8 and 15"),
+        };
+        listed.push(format!("{score} ({cost})"));
+        spent += cost;
+    }
+    if listed.len() != SCORES {
+        return Err("Point buy needs six scores");
+    }
+    let verdict = match spent {
+        _ if spent > BUDGET => format!("{} over budget", spent - BUDGET),
+        _ if spent < BUDGET => format!("legal, {} left", BUDGET - spent),
+        _ => String::from("legal"),
+    };
+    Ok(format!("{} => {spent} of {BUDGET} points, {verdict}", listed.join(", ")))
+}
+
 pub mod input_handling {
 
     use crate::die::D;
+    use crate::error::DiceError;
+    use crate::rng::Backend;
+    use crate::util::{strip_suffix_ignore_case, strip_word_ignore_case};
+
+    use std::fmt;
 
     #[derive(Clone, Copy, PartialEq)]
-    pub enum Code {
+    pub enum Code { // how a command's dice are read
         Normal,
-        Advantage,
-        Disadvantage,
+        Advantage(u16), // number of throws to take the best of
+        Disadvantage(u16), // number of throws to take the worst of
         Percentile,
+        Wild, // Savage Worlds: a trait die and a wild d6, both acing, and the higher counts
+    }
+
+    impl Code {
+        pub fn throws(&self) -> usize { // how many times the command is thrown
+            match self {
+                Code::Advantage(n) | Code::Disadvantage(n) => *n as usize,
+                Code::Wild => 2, // the trait die and the wild die count as throws of their own
+                _ => 1,
+            }
+        }
+    }
+
+    impl fmt::Display for Code {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Code::Normal => write!(f, "Normal"),
+                Code::Advantage(2) => write!(f, "Advantage"),
+                Code::Disadvantage(2) => write!(f, "Disadvantage"),
+                Code::Advantage(n) => write!(f, "Advantage x{n}"),
+                Code::Disadvantage(n) => write!(f, "Disadvantage x{n}"),
+                Code::Percentile => write!(f, "Percentile"),
+                Code::Wild => write!(f, "Wild"),
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Default)]
+    pub struct ThrowOptions { // settings that apply to a whole throw rather than a single command
+        pub time_limit: Option<u16>, // seconds to wait before rolling automatically
+        pub rerolls: Option<u16>, // lock mode: how many times the unlocked dice can be thrown again
+        pub skill_shot: bool, // a reflex minigame before the throw nudges the first command's modifier by -1, 0, or +1
+        pub genchar: bool, // each command is an ability score: the result lists them sorted, with their modifiers
+        pub versus: Option<u16>, // GURPS: the skill the sum has to roll at or under
+        pub split: Option<u16>, // area damage: how many targets take the sum, each able to save for half
+        pub burst: bool, // many expressions on one table: each command gets a colour and its own subtotal
+        pub horde: Option<u16>, // many identical attackers: commands pair up as attack and damage, the attacks against this AC
+        pub no_anim: bool, // dice are set down without rolling, and the results come up without waiting for a key
+        pub blind: bool, // thrown out of sight: kept in the history and CSV, but the result isn't shown
+        pub seed: Option<u64>, // where the dice's randomness comes from, so a throw can be made again exactly. a random one if not set
+        pub rng: Backend, // the generator the seed goes into
+    }
+
+    #[derive(Clone, Copy, PartialEq)]
+    pub enum Keep { // which of a command's dice count towards its total
+        All,
+        Highest(u16),
+        Lowest(u16),
+        DropHighest(u16),
+        DropLowest(u16),
+        Middle, // the median die of an odd number
+    }
+
+    impl Keep {
+        fn count(&self) -> Option<u16> { // the number attached to the rule, if there is one
+            match self {
+                Keep::All | Keep::Middle => None,
+                Keep::Highest(n) | Keep::Lowest(n) | Keep::DropHighest(n) | Keep::DropLowest(n) => Some(*n),
+            }
+        }
+
+        pub fn kept(&self, results: &[i16]) -> Vec<bool> { // flags which of the results (in roll order) are kept
+            let mut order: Vec<usize> = (0..results.len()).collect();
+            order.sort_by_key(|&i| results[i]); // lowest to highest. stable, so ties drop the later die
+            let dropped: Vec<usize> = match *self {
+                Keep::All => Vec::new(),
+                Keep::Highest(n) => order[..results.len().saturating_sub(n as usize)].to_vec(),
+                Keep::Lowest(n) => order[(n as usize).min(results.len())..].to_vec(),
+                Keep::DropHighest(n) => order[results.len().saturating_sub(n as usize)..].to_vec(),
+                Keep::DropLowest(n) => order[..(n as usize).min(results.len())].to_vec(),
+                Keep::Middle => order.iter().copied().filter(|&i| i != order[results.len() / 2]).collect(),
+            };
+            (0..results.len()).map(|i| !dropped.contains(&i)).collect()
+        }
+
+        pub fn total(&self, results: &[i16]) -> i16 { // sum of the kept results
+            results.iter().zip(self.kept(results)).filter(|(_, kept)| *kept).map(|(result, _)| result).sum()
+        }
+    }
+
+    #[derive(Clone, Copy, PartialEq)]
+    pub enum Reroll { // faces that get picked up and thrown again
+        Never,
+        Always(u16), // keep rerolling until the die shows something else
+        Once(u16),
+    }
+
+    impl Reroll {
+        pub fn value(&self) -> Option<u16> { // the face that triggers a reroll, if there is one
+            match self {
+                Reroll::Never => None,
+                Reroll::Always(v) | Reroll::Once(v) => Some(*v),
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, PartialEq)]
+    pub enum Target { // the face a die in a success pool has to reach
+        AtLeast(u16),
+        AtMost(u16),
+    }
+
+    impl Target {
+        pub fn passes(&self, score: i16) -> bool {
+            match self {
+                Target::AtLeast(n) => score >= *n as i16,
+                Target::AtMost(n) => score <= *n as i16,
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, PartialEq)]
+    pub enum Factor { // scales a command's dice total before the modifier is added
+        One,
+        Multiply(u16),
+        Divide(u16), // rounds down
+    }
+
+    impl Factor {
+        pub fn apply(&self, total: i16) -> i16 {
+            match self {
+                Factor::One => total,
+                Factor::Multiply(n) => total.saturating_mul(*n as i16),
+                Factor::Divide(n) => total.div_euclid(*n as i16),
+            }
+        }
+    }
+
+    impl fmt::Display for Factor { // operator and number, e.g. "x 10"
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match self {
+                Factor::One => Ok(()),
+                Factor::Multiply(n) => write!(f, "x {n}"),
+                Factor::Divide(n) => write!(f, "/ {n}"),
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Default, PartialEq)]
+    pub struct Clamp { // bounds a command's dice total, e.g. the "min8" in "3d6min8"
+        pub min: Option<i16>,
+        pub max: Option<i16>,
+    }
+
+    impl Clamp {
+        pub fn apply(&self, total: i16) -> i16 {
+            let total = self.min.map_or(total, |min| total.max(min));
+            self.max.map_or(total, |max| total.min(max))
+        }
+
+        pub fn is_set(&self) -> bool {
+            self.min.is_some() || self.max.is_some()
+        }
+    }
+
+    impl fmt::Display for Clamp { // bound(s) with a word or range, e.g. "min 8" or "3..15"
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match (self.min, self.max) {
+                (Some(min), Some(max)) => write!(f, "{min}..{max}"),
+                (Some(min), None) => write!(f, "min {min}"),
+                (None, Some(max)) => write!(f, "max {max}"),
+                (None, None) => Ok(()),
+            }
+        }
+    }
+
+    #[derive(Clone, PartialEq)]
+    pub struct Modifier { // a flat amount added to a command, e.g. the "+3(str)" in "d20+3(str)+2(prof)"
+        pub value: i16,
+        pub name: Option<String>,
+    }
+
+    impl fmt::Display for Modifier { // sign, number, and name in brackets
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{:+}", self.value)?;
+            if let Some(name) = &self.name {
+                write!(f, "({name})")?;
+            }
+            Ok(())
+        }
+    }
+
+    #[derive(Clone, PartialEq)]
+    pub struct Command { // a single parsed roll command, e.g. "4d6kh3+2"
+        pub label: Option<String>, // e.g. "fire damage", from '2d6+3 # fire damage' or '2d6+3 "fire damage"'
+        pub code: Code,
+        pub coefficient: u16,
+        pub kind: D,
+        pub modifiers: Vec<Modifier>, // added after the dice, in the order written
+        pub keep: Keep,
+        pub explode: bool, // roll another die whenever one lands on its highest face
+        pub reroll: Reroll,
+        pub target: Option<Target>, // makes the command a success pool: dice are counted instead of summed
+        pub botch: bool, // World of Darkness pools: each 1 cancels a success, and 1s with no successes are a botch
+        pub glitch: bool, // Shadowrun pools: 5s and 6s are hits, and half or more 1s are a glitch
+        pub factor: Factor,
+        pub clamp: Clamp, // applied after the factor
+        pub negative: bool, // subtracted from the sum, e.g. the "-1d4" in "2d8-1d4"
+    }
+
+    impl Command {
+        pub fn modifier(&self) -> i16 { // all the modifiers added together
+            self.modifiers.iter().map(|modifier| modifier.value).sum()
+        }
+
+        pub fn itemized(&self) -> bool { // whether the graph lists the modifiers one by one instead of just their sum
+            self.modifiers.len() > 1 || self.modifiers.iter().any(|modifier| modifier.name.is_some())
+        }
+
+        pub fn total(&self, scores: &[i16]) -> i16 { // what the dice come to before the modifier: their sum, or the number of successes in a pool
+            match (self.target, self.botch) {
+                (Some(_), true) => (self.successes(scores) - self.ones(scores)).max(0),
+                (Some(_), false) => self.successes(scores),
+                (None, _) => self.keep.total(scores),
+            }
+        }
+
+        pub fn successes(&self, scores: &[i16]) -> i16 { // kept dice that reach the pool's target
+            self.count_kept(scores, |score| self.target.is_some_and(|target| target.passes(score)))
+        }
+
+        pub fn ones(&self, scores: &[i16]) -> i16 { // kept dice showing 1, which cancel successes in a botch pool
+            self.count_kept(scores, |score| score == 1)
+        }
+
+        fn count_kept(&self, scores: &[i16], counts: impl Fn(i16) -> bool) -> i16 {
+            scores.iter().zip(self.keep.kept(scores)).filter(|(score, kept)| *kept && counts(**score)).count() as i16
+        }
+
+        pub fn dice(&self) -> Vec<D> { // the dice thrown for this command
+            let throw = match (self.code, self.kind) {
+                (Code::Wild, kind) => return vec![kind, D::Six], // the trait die and the wild die
+                (_, D::PercentTens) => vec![D::PercentTens, D::PercentOnes], // extra d10 (manual add)
+                (_, D::PercentHundreds) => vec![D::PercentHundreds, D::PercentTens, D::PercentOnes],
+                (_, kind) => vec![kind; self.coefficient as usize],
+            };
+            throw.repeat(self.code.throws()) // advantage rolls are complete throws to choose between
+        }
+
+        pub fn reach(&self) -> i32 { // the furthest from zero the command can come to, modifier and all -- explosions aside, which have no set end
+            let highest = match self.kind {
+                D::Fudge | D::Coin => 1,
+                _ if self.code == Code::Wild => self.kind.as_number().max(6), // the better of the trait die and the wild die
+                kind => kind.as_number(),
+            } as i32;
+            let dice = if self.target.is_some() { self.coefficient as i32 } else { self.coefficient as i32 * highest }; // a pool counts its dice
+            let scaled = match self.factor {
+                Factor::Multiply(n) => dice * n as i32,
+                _ => dice,
+            };
+            let clamped = self.clamp.max.map_or(scaled.max(self.clamp.min.unwrap_or_default() as i32), |max| (max as i32).abs());
+            clamped + self.modifiers.iter().map(|modifier| modifier.value.unsigned_abs() as i32).sum::<i32>()
+        }
+
+        pub fn value(&self, scores: &[i16]) -> i16 { // what the dice add to the sum, once multiplied/divided, clamped, and signed
+            let value = self.clamp.apply(self.factor.apply(self.total(scores)));
+            if self.negative { -value } else { value }
+        }
+    }
+
+    impl fmt::Display for Command { // how the command is written in the log and on the graph
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            if self.negative {
+                write!(f, "-")?;
+            }
+            match self.code { // written the way get_command_values() reads them back
+                Code::Advantage(3) => write!(f, "adv3 ")?,
+                Code::Advantage(_) => write!(f, "adv ")?,
+                Code::Disadvantage(_) => write!(f, "disadv ")?,
+                Code::Wild => write!(f, "wild ")?,
+                Code::Normal | Code::Percentile => (), // percentile is read off the die
+            }
+            if self.keep == Keep::Middle {
+                write!(f, "mid ")?;
+            }
+            if self.glitch {
+                write!(f, "sr ")?;
+            }
+            match (self.kind, self.coefficient) {
+                (D::Coin, 1) => write!(f, "coin")?,
+                (D::Coin, c) => write!(f, "{c} coins")?,
+                (kind, c) => write!(f, "{c}d{kind}")?,
+            }
+            if self.explode {
+                write!(f, "!")?;
+            }
+            match self.reroll {
+                Reroll::Never => (),
+                Reroll::Always(v) => write!(f, "r{v}")?,
+                Reroll::Once(v) => write!(f, "ro{v}")?,
+            }
+            match self.keep {
+                Keep::All | Keep::Middle => (), // keep-middle is written up front
+                Keep::Highest(n) => write!(f, "kh{n}")?,
+                Keep::Lowest(n) => write!(f, "kl{n}")?,
+                Keep::DropHighest(n) => write!(f, "dh{n}")?,
+                Keep::DropLowest(n) => write!(f, "dl{n}")?,
+            }
+            match self.target {
+                _ if self.glitch => (), // implied by "sr"
+                Some(Target::AtLeast(n)) => write!(f, ">={n}")?,
+                Some(Target::AtMost(n)) => write!(f, "<={n}")?,
+                None => (),
+            }
+            if self.botch {
+                write!(f, " botch")?;
+            }
+            match self.factor {
+                Factor::One => (),
+                Factor::Multiply(n) => write!(f, "*{n}")?,
+                Factor::Divide(n) => write!(f, "/{n}")?,
+            }
+            if let Some(min) = self.clamp.min {
+                write!(f, "min{min}")?;
+            }
+            if let Some(max) = self.clamp.max {
+                write!(f, "max{max}")?;
+            }
+            for modifier in self.modifiers.iter() {
+                write!(f, "{modifier}")?;
+            }
+            Ok(())
+        }
     }
     
-    pub fn generate_dice(input: String) -> Result<(Code, Vec<D>, Vec<i16>, Vec<String>), &'static str> { // take input string and convert to command we can use (list of die and a throw code)
-    
-        // setup
-        const DIE_LIMIT: usize = 99;
-        const ADV_PREFIX: &'static str = "adv";
-        const DISADV_PREFIX: &'static str = "disadv";
-        let input = input.trim().to_lowercase();
-        let inputs: Vec<&str>  = input.split(&[',', '/'][..]).collect(); // command split-by characters
-        let command_count = inputs.len();
+    pub fn get_options(input: &str) -> Result<(ThrowOptions, String), DiceError> { // strips throw-wide prefixes ("timed 10s", "lock 2") and checks ("vs 14") off the input, returning them and what's left
+        const TIMED_PREFIX: &str = "timed";
+        const LOCK_PREFIX: &str = "lock";
+        const SKILL_PREFIX: &str = "skill";
+        const GENCHAR_PREFIX: &str = "genchar";
+        const BURST_PREFIX: &str = "burst";
+        const HORDE_PREFIX: &str = "horde";
+        const VERSUS_SUFFIX: &str = " vs";
+        const SPLIT_PREFIX: &str = "split";
+        const SPLIT_SUFFIX: &str = " among";
+        const TIME_LIMIT: u16 = 99; // seconds
+        const VERSUS_LIMIT: u16 = 99;
+        const TARGET_LIMIT: u16 = 9; // one number key each
+        const REROLL_LIMIT: u16 = 9;
+        const DEFAULT_REROLLS: u16 = 2;
+        let mut options = ThrowOptions::default();
+        let (label, input) = get_flags(input.trim(), &mut options)?;
+        let mut input = input; // case is kept for roll labels
+
+        // a check goes on the end, e.g. "3d6 vs 14"
+        if let Some((rest, skill)) = input.clone().rsplit_once(char::is_whitespace) {
+            if let Some(rest) = strip_suffix_ignore_case(rest.trim_end(), VERSUS_SUFFIX) {
+                options.versus = match skill.parse::<u16>() {
+                    Ok(n) if n > 0 && n <= VERSUS_LIMIT => Some(n),
+                    Ok(_) => return Err(DiceError::OutOfRange { what: "Check", min: 1, max: VERSUS_LIMIT }),
+                    Err(_) => return Err(DiceError::Malformed { part: "Check" }),
+                };
+                input = rest.trim_end().to_string();
+            }
+        }
     
-        // things this function will return
-        let mut code = Code::Normal;
-        let mut dice: Vec<D> = Vec::new(); // D-types
-        let mut modifiers: Vec<i16> = Vec::new(); // note that modifiers don't need to be attached to specific die, just in the right order
-        let mut command_log: Vec<String> = Vec::new();
+        loop { // prefixes can come in any order
+            if let Some(rest) = strip_word_ignore_case(&input, TIMED_PREFIX) {
     
-        for command in inputs {
-            let mut command = command.trim().to_string();
+                // the seconds are the next word, with or without an 's'
+                let (seconds, rest) = rest.trim_start().split_once(' ').ok_or(DiceError::Needs { what: "Timed roll", needs: "a time and a roll, e.g. 'timed 10s 3d6'" })?;
+                options.time_limit = match seconds.trim_end_matches(['s', 'S']).parse::<u16>() {
+                    Ok(s) if s > 0 && s <= TIME_LIMIT => Some(s),
+                    Ok(_) => return Err(DiceError::OutOfRange { what: "Time limit (seconds)", min: 1, max: TIME_LIMIT }),
+                    Err(_) => return Err(DiceError::Malformed { part: "Time limit" }),
+                };
+                input = rest.trim_start().to_string();
+            } else if let Some(rest) = strip_word_ignore_case(&input, LOCK_PREFIX) {
     
-            // identify advantage/disadvantage roll (& remove the prefixes if you find them)
-            if command.starts_with(DISADV_PREFIX) {
-                code = Code::Disadvantage;
-                command = command.strip_prefix(DISADV_PREFIX).unwrap().trim().to_string();
-            } else if command.starts_with(ADV_PREFIX) {
-                code = Code::Advantage;
-                command = command.strip_prefix(ADV_PREFIX).unwrap().trim().to_string();
+                // the reroll count is optional, and only counts as one if the next word is a plain number
+                let rest = rest.trim_start();
+                let (count, remainder) = rest.split_once(' ').unwrap_or((rest, ""));
+                options.rerolls = match count.parse::<u16>() {
+                    Ok(n) if n > 0 && n <= REROLL_LIMIT => {
+                        input = remainder.trim_start().to_string();
+                        Some(n)
+                    },
+                    Ok(_) => return Err(DiceError::OutOfRange { what: "Reroll count", min: 1, max: REROLL_LIMIT }),
+                    Err(_) => {
+                        input = rest.to_string();
+                        Some(DEFAULT_REROLLS)
+                    },
+                };
+            } else if let Some(rest) = strip_word_ignore_case(&input, SKILL_PREFIX) {
+                options.skill_shot = true;
+                input = rest.trim_start().to_string();
+            } else if let Some(rest) = strip_word_ignore_case(&input, SPLIT_PREFIX) {
+
+                // the target count goes on the end, e.g. "split 8d6 among 4"
+                let (roll, count) = rest.trim_end().rsplit_once(char::is_whitespace).ok_or(DiceError::Needs { what: "Split", needs: "a roll and a target count" })?;
+                let roll = strip_suffix_ignore_case(roll.trim_end(), SPLIT_SUFFIX).ok_or(DiceError::Needs { what: "Split", needs: "'among', e.g. 'split 8d6 among 4'" })?;
+                options.split = match count.parse::<u16>() {
+                    Ok(n) if n > 0 && n <= TARGET_LIMIT => Some(n),
+                    Ok(_) => return Err(DiceError::OutOfRange { what: "Target count", min: 1, max: TARGET_LIMIT }),
+                    Err(_) => return Err(DiceError::Malformed { part: "Target count" }),
+                };
+                input = roll.trim().to_string();
+            } else if let Some(rest) = strip_word_ignore_case(&input, BURST_PREFIX) {
+                options.burst = true;
+                input = rest.trim_start().to_string();
+            } else if let Some(rest) = strip_word_ignore_case(&input, GENCHAR_PREFIX) {
+                options.genchar = true;
+                input = get_genchar(rest)?;
+                break; // the rest of the input is the method, not more prefixes
+            } else if let Some(rest) = strip_word_ignore_case(&input, HORDE_PREFIX) {
+                let (ac, rest) = get_horde(rest)?;
+                options.horde = Some(ac);
+                input = rest;
+                break; // likewise the attack and damage
+            } else {
+                break;
             }
-    
-            // get and validate command
-            let (coefficient, kind, modifier) = get_command_values(&command)?;
-            if kind == D::PercentTens {
-                code = Code::Percentile;
+        }
+        if options.skill_shot && options.time_limit.is_some() {
+            return Err(DiceError::Conflict { first: "A skill shot", second: "a time limit" }); // the minigame is what starts the throw
+        }
+        if options.genchar && options.versus.is_some() {
+            return Err(DiceError::Conflict { first: "Ability scores", second: "a check" });
+        }
+        if options.split.is_some() && (options.rerolls.is_some() || options.genchar) {
+            return Err(DiceError::Conflict { first: "Split damage", second: "lock mode or ability scores" }); // the number keys are for saves
+        }
+        if options.horde.is_some() && (options.rerolls.is_some() || options.split.is_some() || options.versus.is_some()) {
+            return Err(DiceError::Conflict { first: "A horde", second: "lock mode, split damage, or a check" }); // the attacks have their own AC to beat
+        }
+        if let Some(label) = label { // given as a flag, it goes on the end the usual way
+            if options.genchar || options.horde.is_some() || split_commands(&input).len() > 1 {
+                return Err(DiceError::Conflict { first: "--label", second: "more than one roll -- label each command with '#' instead" });
             }
-            validate(code, coefficient, kind, modifier, command_count)?;
-    
-            // log commands
-            let mut command_string = String::new();
-            command_string.push_str(&format!("{coefficient}d{}", kind.as_number()));
-            if modifier > 0 {
-                command_string.push_str(&format!("+{}", modifier));
-            } else if modifier < 0 {
-                command_string.push_str(&format!("{}", modifier));
-            }
-            command_log.push(command_string);
-    
-            // load dice and modifiers in vectors
-            modifiers.push(modifier);
-            match code {
-                Code::Normal => {
-                    for _ in 1..=coefficient {
-                        dice.push(kind);
-                    }
+            if input.contains(['#', '"']) {
+                return Err(DiceError::Conflict { first: "--label", second: "another label" });
+            }
+            input = format!("{input} # {label}");
+        }
+        Ok((options, input))
+    }
+
+    fn get_flags(input: &str, options: &mut ThrowOptions) -> Result<(Option<String>, String), DiceError> { // strips per-roll flags off the end ("2d6+3 --fast --blind --label \"trap dmg\""), setting them on the options, and returns the label given (if any) with what's left
+        let start = input.match_indices("--").map(|(i, _)| i).find(|i| {
+            (*i == 0 || input[..*i].ends_with(char::is_whitespace)) && input[i + 2..].starts_with(|c: char| c.is_ascii_alphabetic())
+        });
+        let Some(start) = start else { return Ok((None, input.to_string())) };
+        if input[..start].contains('#') || input[..start].matches('"').count() % 2 == 1 {
+            return Ok((None, input.to_string())); // it's part of a label
+        }
+        let mut label = None;
+        let mut rest = input[start..].trim();
+        while !rest.is_empty() {
+            let (flag, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            let after = after.trim_start();
+            rest = match flag.to_lowercase().as_str() {
+                "--fast" => {
+                    options.no_anim = true;
+                    after
+                },
+                "--blind" => {
+                    options.blind = true;
+                    after
                 },
-                Code::Advantage | Code::Disadvantage => {
-                    dice.push(kind);
-                    dice.push(kind);
+                "--seed" => {
+                    let (seed, after) = after.split_once(char::is_whitespace).unwrap_or((after, ""));
+                    options.seed = Some(seed.parse::<u64>().map_err(|_| DiceError::Needs { what: "--seed", needs: "a whole number, e.g. '--seed 42'" })?);
+                    after.trim_start()
                 },
-                Code::Percentile => {
-                    dice.push(D::PercentTens); // could also just say "kind" here
-                    dice.push(D::PercentOnes); // extra d10 (manual add)
+                "--label" => {
+                    let (text, after) = match after.strip_prefix('"') {
+                        Some(quoted) => quoted.split_once('"').ok_or(DiceError::Malformed { part: "Label" })?,
+                        None => after.split_once(char::is_whitespace).unwrap_or((after, "")),
+                    };
+                    if text.trim().is_empty() {
+                        return Err(DiceError::Needs { what: "--label", needs: "some text, e.g. '--label \"trap dmg\"'" });
+                    }
+                    label = Some(text.trim().to_string());
+                    after.trim_start()
                 },
+                _ => return Err(DiceError::UnknownFlag { flag: flag.to_string() }),
+            };
+        }
+        Ok((label, input[..start].trim_end().to_string()))
+    }
+    
+    pub fn generate_dice(input: String, options: &ThrowOptions) -> Result<(Vec<D>, Vec<Command>), DiceError> { // take input string and convert to commands we can use (list of die and the commands they belong to)
+    
+        // setup
+        let input = input.trim();
+        let inputs = split_commands(input); // command split-by characters
+        let mut command_log: Vec<Command> = Vec::new(); // each command carries its own code, modifier and keep/drop rule
+    
+        for command in inputs {
+    
+            // get and validate command
+            let (repeats, command) = match get_repeat(&command) {
+                Some(r) => r,
+                None => return Err(DiceError::Malformed { part: "Repeat" }),
+            };
+            let command = get_command_values(&command)?;
+            validate(&command)?;
+            if repeats > 1 && command.reach() * repeats as i32 > i16::MAX as i32 { // each repetition adds its whole total to the sum
+                return Err(DiceError::LimitExceeded { what: "Repeat" });
+            }
+
+            // a repeated command is logged once per repetition, numbered so each gets its own subtotal
+            for n in 1..=repeats {
+                let mut command = command.clone();
+                if repeats > 1 {
+                    command.label = Some(match &command.label {
+                        Some(label) => format!("{label} {n}"),
+                        None => format!("#{n}"),
+                    });
+                }
+                command_log.push(command);
             }
         }
+        load_dice(command_log, options)
+    }
+
+    pub(crate) fn load_dice(command_log: Vec<Command>, options: &ThrowOptions) -> Result<(Vec<D>, Vec<Command>), DiceError> { // the dice for commands that have already been validated (by generate_dice, or built), checking the limits on the throw as a whole
+        const DIE_LIMIT: usize = 99;
+        let dice: Vec<D> = command_log.iter().flat_map(|command| command.dice()).collect();
+
+        // subtracted dice need something to come off of
+        if command_log.first().is_some_and(|command| command.negative) {
+            return Err(DiceError::Needs { what: "Subtracted dice", needs: "another roll before them" });
+        }
     
         // limit check
         if dice.len() > DIE_LIMIT {
-            return Err("Cannot roll this many die");
+            return Err(DiceError::TooManyDice { limit: DIE_LIMIT });
+        }
+        let crits = if options.horde.is_some() { 2 } else { 1 }; // a horde's critical hits double the damage dice
+        if command_log.iter().map(|command| command.reach() * crits).sum::<i32>() > i16::MAX as i32 { // the sum has to fit, however the dice land
+            return Err(DiceError::LimitExceeded { what: "Total" });
+        }
+
+        // lock mode picks dice out by number keys, so keep it to one plain command of up to nine dice
+        if options.rerolls.is_some() {
+            if command_log.len() != 1 || command_log[0].code != Code::Normal || dice.len() < 2 || dice.len() > 9 {
+                return Err(DiceError::Needs { what: "Lock mode", needs: "a single roll of 2 to 9 dice" });
+            }
+            if command_log[0].explode {
+                return Err(DiceError::Conflict { first: "Lock mode", second: "exploding dice" });
+            }
+        }
+
+        // a GURPS check reads its criticals off the three dice, so it has to be just those
+        if options.versus.is_some() {
+            let single_3d6 = matches!(&command_log[..], [command] if command.coefficient == 3 && command.kind == D::Six && command.code == Code::Normal
+                && command.keep == Keep::All && !command.explode && command.reroll == Reroll::Never && command.target.is_none() && command.factor == Factor::One && !command.clamp.is_set());
+            if !single_3d6 {
+                return Err(DiceError::Needs { what: "A check", needs: "a single 3d6, e.g. '3d6+2 vs 14'" });
+            }
         }
     
-        Ok((code, dice, modifiers, command_log))
+        Ok((dice, command_log))
     }
     
-    pub fn get_command_values(input: &String) -> Result<(u16, D, i16), &'static str> { // gets all command values in one go. Accepts "CdK+M" format
-        let coefficient = match get_coefficient(&input) {
+    fn get_genchar(input: &str) -> Result<String, DiceError> { // expands "genchar" (or "genchar 3d6 x7") into one command per ability score
+        const DEFAULT_METHOD: &str = "4d6kh3";
+        const DEFAULT_SCORES: usize = 6;
+        const SCORE_LIMIT: usize = 9;
+        let input = input.trim();
+        let (method, count) = match input.rsplit_once(char::is_whitespace).unwrap_or(("", input)) { // the score count is an optional last word, e.g. "x6"
+            (method, count) if count.starts_with(['x', 'X']) => match count[1..].parse::<usize>() {
+                Ok(n) if n > 0 && n <= SCORE_LIMIT => (method.trim(), n),
+                _ => return Err(DiceError::OutOfRange { what: "Ability score count", min: 1, max: SCORE_LIMIT as u16 }),
+            },
+            _ => (input, DEFAULT_SCORES),
+        };
+        let method = if method.is_empty() { DEFAULT_METHOD } else { method };
+        if method.contains([',', '#', '"']) {
+            return Err(DiceError::Needs { what: "Character generation", needs: "a single roll method" });
+        }
+        Ok(vec![method; count].join(", "))
+    }
+
+    fn get_horde(input: &str) -> Result<(u16, String), DiceError> { // expands "horde 8 atk d20+4 ac 15 dmg 1d6+2" into an attack and a damage command per attacker, returning the AC with them
+        const HORDE_LIMIT: u16 = 20;
+        const AC_LIMIT: u16 = 99;
+        const USAGE: DiceError = DiceError::Needs { what: "Horde", needs: "a count, attack, AC, and damage, e.g. 'horde 8 atk d20+4 ac 15 dmg 1d6+2'" };
+        let input = input.trim().to_lowercase();
+        let (count, rest) = input.split_once(char::is_whitespace).ok_or(USAGE)?;
+        let count = match count.parse::<u16>() {
+            Ok(n) if n > 0 && n <= HORDE_LIMIT => n,
+            Ok(_) => return Err(DiceError::OutOfRange { what: "Horde attackers", min: 1, max: HORDE_LIMIT }),
+            Err(_) => return Err(DiceError::Malformed { part: "Horde count" }),
+        };
+        let rest = rest.trim_start().strip_prefix("atk").ok_or(USAGE)?;
+        let (attack, rest) = rest.split_once(" ac ").ok_or(USAGE)?;
+        let (ac, damage) = rest.trim_start().split_once(" dmg ").ok_or(USAGE)?;
+        let ac = match ac.trim().parse::<u16>() {
+            Ok(n) if n > 0 && n <= AC_LIMIT => n,
+            Ok(_) => return Err(DiceError::OutOfRange { what: "Horde AC", min: 1, max: AC_LIMIT }),
+            Err(_) => return Err(DiceError::Malformed { part: "Horde AC" }),
+        };
+        let (attack, damage) = (attack.trim(), damage.trim());
+        if split_commands(attack).len() != 1 || split_commands(damage).len() != 1 || attack.contains(['#', '"']) || damage.contains(['#', '"']) {
+            return Err(DiceError::Needs { what: "Horde attack and damage", needs: "a single roll each" }); // they pair up by position
+        }
+        let check = get_command_values(attack)?;
+        if check.kind != D::Twenty || check.coefficient != 1 || check.keep != Keep::All || check.target.is_some()
+            || !matches!(check.code, Code::Normal | Code::Advantage(_) | Code::Disadvantage(_)) {
+            return Err(DiceError::Needs { what: "Horde attacks", needs: "a d20, with or without advantage" }); // the natural roll decides crits
+        }
+        Ok((ac, vec![format!("{attack}, {damage}"); count as usize].join(", ")))
+    }
+
+    fn split_commands(input: &str) -> Vec<String> { // splits on commas, and on slashes and minuses followed by dice ("2d6/d8", "2d8-1d4") -- not ones that divide or subtract a constant ("8d6/2", "d20-1"), or ones inside a label ("d20 # hit, then run"), unless a '#' label is followed by more dice ("d20 # hit, 2d6 # damage")
+        let mut commands: Vec<String> = Vec::new();
+        let mut command = String::new();
+        let (mut quoted, mut commented) = (false, false); // whether we're inside a label
+        for (i, c) in input.char_indices() {
+            match c {
+                '"' => quoted = !quoted,
+                '#' if !quoted => commented = true,
+                _ => (),
+            }
+            let after = &input[i + c.len_utf8()..];
+            if c == ',' && !quoted && (!commented || starts_with_command(after)) {
+                commands.push(command);
+                command = String::new();
+                commented = false;
+                continue;
+            }
+            if (c == '/' || c == '-') && !quoted && !commented && !command.trim().is_empty() && starts_with_dice(after) {
+                commands.push(command);
+                command = String::new();
+                if c == '-' {
+                    command.push(c); // subtracted dice keep their sign
+                }
+                continue;
+            }
+            command.push(c);
+        }
+        commands.push(command);
+        commands
+    }
+
+    fn get_repeat(input: &str) -> Option<(u16, String)> { // analyzes a command for a repeat ("3x(2d6+1)"), returning the count and the command inside the brackets (with anything after them, like a label)
+        const REPEAT_LIMIT: u16 = 20;
+        let trimmed = input.trim_start();
+        let digits = trimmed.find(|c: char| !c.is_ascii_digit()).unwrap_or(trimmed.len());
+        let rest = match trimmed[digits..].strip_prefix(['x', 'X']) {
+            Some(rest) if digits > 0 && rest.trim_start().starts_with('(') => rest.trim_start(),
+            _ => return Some((1, input.to_string())), // not a repeat
+        };
+        let count = trimmed[..digits].parse::<u16>().ok()?;
+        if count == 0 || count > REPEAT_LIMIT {
+            return None;
+        }
+        let end = rest.find(')')?; // brackets must be closed
+        Some((count, format!("{}{}", &rest[1..end], &rest[end + 1..])))
+    }
+
+    fn starts_with_dice(input: &str) -> bool { // whether a slice opens with a dice term, e.g. "d8", "2d8", "d%", or "4dF" -- not a word like "dmg"
+        let input = input.trim_start();
+        let digits = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+        let Some(kind) = input[digits..].strip_prefix(['d', 'D']) else { return false };
+        match kind.chars().next() {
+            Some(c) if c.is_ascii_digit() || c == '%' => true,
+            Some('f' | 'F' | 'c' | 'C') => !kind[1..].starts_with(|c: char| c.is_alphabetic()),
+            _ => false,
+        }
+    }
+
+    fn starts_with_command(input: &str) -> bool { // whether a slice opens a whole command: dice, maybe after a prefix ("adv d20", "-1d4", "sr 5d6"), a repeat ("3x(d6)"), or coins
+        let input = input.trim_start().to_lowercase();
+        let mut rest = input.strip_prefix('-').unwrap_or(&input).trim_start();
+        for prefix in ["disadv", "adv3", "adv", "wild", "mid", "sr"] {
+            if let Some(after) = rest.strip_prefix(prefix) {
+                rest = after.trim_start();
+            }
+        }
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let after_count = rest[digits..].trim_start();
+        starts_with_dice(rest) || (digits > 0 && after_count.starts_with("x(")) || ["coin", "flip"].iter().any(|word| after_count.starts_with(word))
+    }
+    
+    pub fn get_command_values(input: &str) -> Result<Command, DiceError> { // gets all command values in one go. Accepts "CdK+M" format, with an optional keep/drop rule after the kind
+        const ADV_PREFIX: &str = "adv";
+        const ADV3_PREFIX: &str = "adv3 "; // space needed, otherwise "adv3d6" would be ambiguous
+        const MID_PREFIX: &str = "mid";
+        const DISADV_PREFIX: &str = "disadv";
+        const SHADOWRUN_PREFIX: &str = "sr";
+        const WILD_PREFIX: &str = "wild";
+        let (label, input) = get_label(input)?; // the label keeps its case, the rest doesn't need it
+        let input = input.to_lowercase();
+        let (weighted, input) = match crate::weighted::take(&input) { // a weighted die's name stands in for its side count while the rest is read
+            Some((kind, rest)) => (Some(kind), rest),
+            None => (None, input),
+        };
+        let (negative, input) = match input.trim_start().strip_prefix('-') { // a leading minus subtracts the whole command
+            Some(rest) => (true, rest),
+            None => (false, &input[..]),
+        };
+        let input = input.trim_start();
+        let (mut code, input) = if let Some(rest) = input.strip_prefix(DISADV_PREFIX) { // identify advantage/disadvantage roll (& remove the prefixes if you find them)
+            (Code::Disadvantage(2), rest)
+        } else if let Some(rest) = input.strip_prefix(ADV3_PREFIX) {
+            (Code::Advantage(3), rest)
+        } else if let Some(rest) = input.strip_prefix(ADV_PREFIX) {
+            (Code::Advantage(2), rest)
+        } else if let Some(rest) = input.strip_prefix(WILD_PREFIX) {
+            (Code::Wild, rest)
+        } else {
+            (Code::Normal, input)
+        };
+        let (middle, input) = match input.trim_start().strip_prefix(MID_PREFIX) {
+            Some(rest) => (true, rest),
+            None => (false, input),
+        };
+        let (glitch, input) = match input.trim_start().strip_prefix(SHADOWRUN_PREFIX) {
+            Some(rest) => (true, rest),
+            None => (false, input),
+        };
+        let input = get_coins(input);
+        let (input, modifiers) = match input.find(['+', '-']) { // modifiers are everything from the first sign on, so their names don't confuse the other parsers
+            Some(start) => match get_modifiers(&input[start..]) {
+                Some(m) => (&input[..start], m),
+                None => return Err(DiceError::Malformed { part: "Modifier" }),
+            },
+            None => (&input[..], Vec::new()),
+        };
+        let (clamp, input) = match get_clamp(input) { // "min"/"max" are words, so they go before the single-letter parsers see them
             Some(c) => c,
-            None => return Err("Coefficient error"),
+            None => return Err(DiceError::Malformed { part: "Min/max" }),
+        };
+        let (botch, input) = match get_botch(&input) {
+            Some(b) => b,
+            None => return Err(DiceError::Malformed { part: "Botch" }),
         };
-        let kind = match get_kind(&input) {
+        let (keep, input) = match get_keep(&input) { // strip the keep/drop rule off first so it doesn't confuse the other parsers
+            Some((Keep::All, rest)) if middle => (Keep::Middle, rest),
+            Some(_) if middle => return Err(DiceError::Conflict { first: "Keep-middle", second: "another keep/drop rule" }),
             Some(k) => k,
-            None => return Err("Die type error"),
+            None => return Err(DiceError::Malformed { part: "Keep/drop" }),
         };
-        let modifier = match get_modifier(&input) {
-            Some(m) => m,
-            None => return Err("Modifier error"),
+        let (explode, input) = match get_explode(&input) {
+            Some((e, rest)) => (e || code == Code::Wild, rest), // wild rolls always ace
+            None => return Err(DiceError::Malformed { part: "Explosion" }),
         };
-        Ok((coefficient, kind, modifier))
+        let (reroll, input) = match get_reroll(&input) {
+            Some(r) => r,
+            None => return Err(DiceError::Malformed { part: "Reroll" }),
+        };
+        let (target, input) = match get_target(&input) {
+            Some((None, rest)) if glitch => (Some(Target::AtLeast(5)), rest), // hits are 5s and 6s
+            Some(_) if glitch => return Err(DiceError::Conflict { first: "A Shadowrun pool", second: "a target number -- it already counts 5s and 6s" }),
+            Some(t) => t,
+            None => return Err(DiceError::Malformed { part: "Target number" }),
+        };
+        let (factor, input) = match get_factor(&input) {
+            Some(f) => f,
+            None => return Err(DiceError::Malformed { part: "Multiplier/divisor" }),
+        };
+        let coefficient = match get_coefficient(&input) {
+            Some(c) => c,
+            None => return Err(DiceError::InvalidCoefficient),
+        };
+        let kind = match weighted.or_else(|| get_kind(&input)) {
+            Some(k) => k,
+            None => return Err(DiceError::UnknownDie { input }),
+        };
+        if matches!(kind, D::PercentTens | D::PercentHundreds) && code == Code::Normal {
+            code = Code::Percentile;
+        }
+        Ok(Command { label, code, coefficient, kind, modifiers, keep, explode, reroll, target, botch, glitch, factor, clamp, negative })
     }
     
+    fn get_label(input: &str) -> Result<(Option<String>, String), DiceError> { // analyzes a slice for a label ('# fire damage' or '"attack"'), returning it and the input with it removed
+        const LABEL_LIMIT: usize = 24;
+        let (label, rest) = match (input.find('#'), input.find('"')) {
+            (Some(start), quote) if quote.is_none_or(|q| start < q) => (&input[start + 1..], input[..start].to_string()), // everything after the '#'
+            (_, Some(start)) => {
+                let end = start + 1 + input[start + 1..].find('"').ok_or(DiceError::Malformed { part: "Label" })?; // quotes must be closed
+                (&input[start + 1..end], format!("{}{}", &input[..start], &input[end + 1..]))
+            },
+            (_, None) => return Ok((None, input.to_string())),
+        };
+        let label = label.trim();
+        if label.is_empty() {
+            return Err(DiceError::Malformed { part: "Label" });
+        }
+        if label.chars().count() > LABEL_LIMIT {
+            return Err(DiceError::LabelTooLong { limit: LABEL_LIMIT });
+        }
+        Ok((Some(label.to_string()), rest))
+    }
+
     fn get_coefficient(input: &String) -> Option<u16> { // analyzes a slice for a coefficient. must be first thing in input, besides whitespace
         if !input.contains('d') { // safety: rejects if there's no 'd'
             return None;
@@ -256,65 +1282,548 @@ pub mod input_handling {
         if d_str.trim() == "%" { // this might need an escape to work
             return Some(D::PercentTens);
         }
+        if d_str.trim() == "f" { // input is lowercased by this point
+            return Some(D::Fudge);
+        }
+        if d_str.trim() == "c" {
+            return Some(D::Coin);
+        }
         let die = match d_str.trim().parse::<u16>() {
             Ok(2) => D::Two,
             Ok(4) => D::Four,
             Ok(6) => D::Six,
+            Ok(8) => D::Eight,
             Ok(10) => D::Ten,
             Ok(12) => D::Twelve,
             Ok(20) => D::Twenty,
             Ok(100) => D::PercentTens, // when this is returned, the dice generator manually tosses a PercentOnes as well
+            Ok(1000) => D::PercentHundreds, // and a PercentTens and PercentOnes here
+            Ok(sides) if sides > 0 => D::Custom(sides), // anything else non-standard (validate() checks the size)
             _ => return None,
         };
         Some(die)
     }
     
-    fn get_modifier(input: &String) -> Option<i16> { // analyzes a slice for a modifier
-        let mut operations = input.clone(); // safety: rejects input if it contains more than one modifier attempt
-        operations.retain(|c| c == '+' || c == '-');
-        if operations.chars().count() > 1 {
-            return None; // this really is more of an "error", but going for concision here
+    fn get_modifiers(input: &str) -> Option<Vec<Modifier>> { // analyzes the end of a command for modifiers, each with an optional name ("+3(str) -1(cover)")
+        let mut modifiers = Vec::new();
+        let mut rest = input.trim();
+        while !rest.is_empty() {
+            let sign = match rest.chars().next()? {
+                '+' => 1,
+                '-' => -1,
+                _ => return None, // modifiers must follow one another
+            };
+            rest = rest[1..].trim_start();
+            let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            let value = rest[..end].parse::<i16>().ok()?;
+            rest = rest[end..].trim_start();
+            let name = match rest.strip_prefix('(') {
+                Some(named) => {
+                    let close = named.find(')')?;
+                    let name = named[..close].trim();
+                    if name.is_empty() {
+                        return None;
+                    }
+                    rest = named[close + 1..].trim_start();
+                    Some(name.to_string())
+                },
+                None => None,
+            };
+            modifiers.push(Modifier { value: sign * value, name });
+        }
+        Some(modifiers)
+    }
+    
+    fn get_keep(input: &str) -> Option<(Keep, String)> { // analyzes a slice for a keep/drop rule ("kh3", "kl1", "dh1", "dl1"), returning it and the input with the rule removed
+        let rules = ["kh", "kl", "dh", "dl"];
+        let found: Vec<(usize, &str)> = rules.iter().filter_map(|rule| Some((input.find(rule)?, *rule))).collect();
+        if found.is_empty() {
+            return Some((Keep::All, input.to_string()));
+        }
+        if found.len() > 1 {
+            return None; // only one rule per command
+        }
+        let (start, rule) = found[0];
+    
+        // the count runs from the end of the rule to the next non-digit (or the end of the command). no count means 1
+        let rest = &input[start + rule.len()..];
+        let end = rest.find(|c: char| !c.is_ascii_digit() && !c.is_whitespace()).unwrap_or(rest.len());
+        let count = match rest[..end].trim() {
+            "" => 1,
+            count => count.parse::<u16>().ok()?,
+        };
+        let keep = match rule {
+            "kh" => Keep::Highest(count),
+            "kl" => Keep::Lowest(count),
+            "dh" => Keep::DropHighest(count),
+            _ => Keep::DropLowest(count),
+        };
+        let remainder = format!("{}{}", &input[..start], &rest[end..]);
+        Some((keep, remainder))
+    }
+    
+    fn get_explode(input: &str) -> Option<(bool, String)> { // analyzes a slice for the exploding marker ('!'), returning it and the input with the marker removed
+        match input.matches('!').count() {
+            0 => Some((false, input.to_string())),
+            1 => Some((true, input.replacen('!', "", 1))),
+            _ => None,
+        }
+    }
+    
+    fn get_reroll(input: &str) -> Option<(Reroll, String)> { // analyzes a slice for a reroll rule ("r1" or "ro1"), returning it and the input with the rule removed
+        let start = match input.find('r') {
+            Some(start) => start,
+            None => return Some((Reroll::Never, input.to_string())),
+        };
+        let (once, rest) = match input[start + 1..].strip_prefix('o') {
+            Some(rest) => (true, rest),
+            None => (false, &input[start + 1..]),
+        };
+        if rest.contains('r') {
+            return None; // only one rule per command
         }
     
-        // find operator and parse what comes after it. modifier must be last thing in slice for this to work
-        if input.contains('+') {
-            match input.split('+').last()?.trim().parse::<i16>() {
-                Ok(modifier) => return Some(modifier),
-                Err(_) => return None, // returns None if whatever follows the operator is not parseable
+        // the face runs up to the next non-digit (or the end of the command) and has to be there
+        let end = rest.find(|c: char| !c.is_ascii_digit() && !c.is_whitespace()).unwrap_or(rest.len());
+        let value = rest[..end].trim().parse::<u16>().ok()?;
+        let reroll = match once {
+            true => Reroll::Once(value),
+            false => Reroll::Always(value),
+        };
+        let remainder = format!("{}{}", &input[..start], &rest[end..]);
+        Some((reroll, remainder))
+    }
+    
+    fn get_target(input: &str) -> Option<(Option<Target>, String)> { // analyzes a slice for a success pool target (">=7", ">7", "<=3", "<3"), returning it and the input with the target removed
+        let start = match input.find(['>', '<']) {
+            Some(start) => start,
+            None => return Some((None, input.to_string())),
+        };
+        let comparison = if input[start + 1..].starts_with('=') { &input[start..start + 2] } else { &input[start..start + 1] };
+        let rest = &input[start + comparison.len()..];
+        if rest.contains(['>', '<']) {
+            return None; // only one target per command
+        }
+    
+        // the number runs up to the next non-digit (or the end of the command) and has to be there
+        let end = rest.find(|c: char| !c.is_ascii_digit() && !c.is_whitespace()).unwrap_or(rest.len());
+        let value = rest[..end].trim().parse::<u16>().ok()?;
+        let target = match comparison {
+            ">=" => Target::AtLeast(value),
+            ">" => Target::AtLeast(value.checked_add(1)?),
+            "<=" => Target::AtMost(value),
+            _ => Target::AtMost(value.checked_sub(1)?),
+        };
+        let remainder = format!("{}{}", &input[..start], &rest[end..]);
+        Some((Some(target), remainder))
+    }
+    
+    fn get_factor(input: &str) -> Option<(Factor, String)> { // analyzes a slice for a multiplier ("*10") or divisor ("/2"), returning it and the input with it removed
+        let start = match input.find(['*', '/']) {
+            Some(start) => start,
+            None => return Some((Factor::One, input.to_string())),
+        };
+        let rest = &input[start + 1..];
+        if rest.contains(['*', '/']) {
+            return None; // only one per command
+        }
+    
+        // the number runs up to the next non-digit (or the end of the command) and has to be there
+        let end = rest.find(|c: char| !c.is_ascii_digit() && !c.is_whitespace()).unwrap_or(rest.len());
+        let value = rest[..end].trim().parse::<u16>().ok()?;
+        let factor = match &input[start..start + 1] {
+            "*" => Factor::Multiply(value),
+            _ => Factor::Divide(value),
+        };
+        let remainder = format!("{}{}", &input[..start], &rest[end..]);
+        Some((factor, remainder))
+    }
+    
+    fn get_coins(input: &str) -> String { // turns "coin" or "flip" (and "5 coins", "5 flips") into coin dice, e.g. "5dc"
+        for word in ["coins", "flips", "coin", "flip"] {
+            if let Some(start) = input.find(word) {
+                return format!("{}dc{}", &input[..start], &input[start + word.len()..]);
             }
         }
-        if input.contains('-') {
-            match input.split('-').last()?.trim().parse::<i16>() {
-                Ok(value) => return Some(0 - value),
-                Err(_) => return None,
+        input.to_string()
+    }
+
+    fn get_botch(input: &str) -> Option<(bool, String)> { // analyzes a slice for the botch keyword, returning it and the input with it removed
+        const BOTCH: &str = "botch";
+        match input.matches(BOTCH).count() {
+            0 => Some((false, input.to_string())),
+            1 => Some((true, input.replacen(BOTCH, "", 1))),
+            _ => None,
+        }
+    }
+
+    fn get_clamp(input: &str) -> Option<(Clamp, String)> { // analyzes a slice for a lower ("min8") and/or upper ("max15") bound, returning them and the input with them removed
+        let mut clamp = Clamp::default();
+        let mut input = input.to_string();
+        for word in ["min", "max"] {
+            let start = match input.find(word) {
+                Some(start) => start,
+                None => continue,
+            };
+            let rest = &input[start + word.len()..];
+            if rest.contains(word) {
+                return None; // only one of each per command
+            }
+
+            // the number runs up to the next non-digit (or the end of the command) and has to be there
+            let end = rest.find(|c: char| !c.is_ascii_digit() && !c.is_whitespace()).unwrap_or(rest.len());
+            let value = rest[..end].trim().parse::<i16>().ok()?;
+            match word {
+                "min" => clamp.min = Some(value),
+                _ => clamp.max = Some(value),
             }
+            input = format!("{}{}", &input[..start], &rest[end..]);
         }
-        Some(0) // if no operator is found, modifier is zero
+        Some((clamp, input))
     }
     
-    fn validate(code: Code, coefficient: u16, kind: D, modifier: i16, command_count: usize) -> Result<(), &'static str> { // validates pending commands
+    pub(crate) fn validate(command: &Command) -> Result<(), DiceError> { // validates pending commands
         
         const COEFFICIENT_LIMIT: usize = 99;
-        const MODIFIER_LIMIT: usize = 99; // absolute value
+        const MODIFIER_LIMIT: usize = 99; // absolute value, each and all together
+        const MODIFIER_COUNT_LIMIT: usize = 5;
+        const FACTOR_LIMIT: usize = 99;
+        const TOTAL_LIMIT: usize = 9999; // the graph has room for four digits
+        let Command { code, coefficient, kind, keep, explode, reroll, target, botch, glitch, factor, clamp, negative, .. } = *command;
     
         if coefficient == 0 {
-            return Err("Coefficient cannot be zero");
+            return Err(DiceError::ZeroCoefficient);
         }
         if coefficient as usize > COEFFICIENT_LIMIT {
-            return Err("Coefficient limit exceeded");
+            return Err(DiceError::LimitExceeded { what: "Coefficient" });
+        }
+        if command.modifier().unsigned_abs() as usize > MODIFIER_LIMIT || command.modifiers.iter().any(|m| m.value.unsigned_abs() as usize > MODIFIER_LIMIT) {
+            return Err(DiceError::LimitExceeded { what: "Modifier" });
+        }
+        if command.modifiers.len() > MODIFIER_COUNT_LIMIT {
+            return Err(DiceError::TooManyModifiers { limit: MODIFIER_COUNT_LIMIT });
+        }
+        if let D::Custom(sides) = kind {
+            if sides == 0 {
+                return Err(DiceError::NoSides);
+            }
+        }
+        if coefficient as usize * kind.as_number() as usize > TOTAL_LIMIT { // any number of sides, so long as the dice can't come to more than the graph shows
+            return Err(DiceError::LimitExceeded { what: "Dice total" });
         }
-        if modifier.abs() as usize > MODIFIER_LIMIT {
-            return Err("Modifier limit exceeded");
+        if matches!(kind, D::PercentTens | D::PercentHundreds) && coefficient != 1 {
+            return Err(DiceError::NotAllowed { what: "have a coefficient" });
         }
-        if code != Code::Normal && coefficient != 1 {
-            return Err("You cannot have a coefficient on this roll");
+        if code == Code::Percentile && keep != Keep::All {
+            return Err(DiceError::NotAllowed { what: "keep or drop dice" });
         }
-        if (code == Code::Advantage || code == Code::Disadvantage) && kind == D::PercentTens {
-            return Err("You cannot roll advantage/disadvantage on a d100"); // really it should maybe be "anything but d20"?
+        if !matches!(code, Code::Normal | Code::Wild) && explode {
+            return Err(DiceError::NotAllowed { what: "explode dice" });
         }
-        if code != Code::Normal && command_count != 1 {
-            return Err("You cannot throw extra die on advantage, disadvantage, and percentile rolls"); // pass in vector.len() for count
+        if code == Code::Wild && (coefficient != 1 || keep != Keep::All || matches!(kind, D::Fudge | D::PercentTens | D::PercentHundreds)) {
+            return Err(DiceError::Needs { what: "A wild roll", needs: "one trait die, e.g. 'wild d8'" });
+        }
+        if explode && keep != Keep::All {
+            return Err(DiceError::Conflict { first: "Exploding dice", second: "a keep/drop rule" });
+        }
+        if explode && kind.as_number() < 2 {
+            return Err(DiceError::Endless { what: "explode" });
+        }
+        if code != Code::Normal && reroll != Reroll::Never {
+            return Err(DiceError::NotAllowed { what: "reroll dice" });
+        }
+        if matches!(kind, D::Fudge | D::Coin) && (explode || reroll != Reroll::Never) {
+            return Err(DiceError::NotAllowed { what: "explode or reroll fudge dice or coins" });
+        }
+        if let Some(value) = reroll.value() {
+            if value == 0 || value > kind.as_number() {
+                return Err(DiceError::NotOnDie { what: "Reroll face" });
+            }
+            if kind.as_number() < 2 {
+                return Err(DiceError::Endless { what: "reroll" });
+            }
+        }
+        if code != Code::Normal && target.is_some() {
+            return Err(DiceError::NotAllowed { what: "count successes" });
+        }
+        if let Some(Target::AtLeast(value) | Target::AtMost(value)) = target {
+            let scores = (1..=kind.as_number()).map(|face| kind.score(face)); // what the faces count for, e.g. -1 to 1 for Fudge dice
+            let (lowest, highest) = (scores.clone().min().unwrap_or_default(), scores.max().unwrap_or_default());
+            if !matches!(kind, D::PercentTens | D::PercentHundreds) && !(lowest as i32..=highest as i32).contains(&(value as i32)) {
+                return Err(DiceError::NotOnDie { what: "Success target" });
+            }
+        }
+        if botch && !matches!(target, Some(Target::AtLeast(_))) {
+            return Err(DiceError::Needs { what: "Botch", needs: "a success pool with a minimum, e.g. '>=6'" });
+        }
+        if botch && kind == D::Fudge {
+            return Err(DiceError::NotAllowed { what: "botch fudge dice" });
+        }
+        if glitch && (kind != D::Six || botch) {
+            return Err(DiceError::Needs { what: "A Shadowrun pool", needs: "plain d6s, e.g. 'sr 12d6'" });
+        }
+        match factor {
+            _ if factor != Factor::One && code != Code::Normal => return Err(DiceError::NotAllowed { what: "multiply or divide" }),
+            Factor::Multiply(0) | Factor::Divide(0) => return Err(DiceError::DivideByZero),
+            Factor::Multiply(n) | Factor::Divide(n) if n as usize > FACTOR_LIMIT => return Err(DiceError::LimitExceeded { what: "Multiplier/divisor" }),
+            Factor::Multiply(n) if coefficient as usize * kind.as_number() as usize * n as usize > TOTAL_LIMIT => return Err(DiceError::LimitExceeded { what: "Multiplied total" }),
+            _ => (),
+        }
+        if clamp.is_set() && code != Code::Normal {
+            return Err(DiceError::NotAllowed { what: "clamp the total" });
+        }
+        if let (Some(min), Some(max)) = (clamp.min, clamp.max) {
+            if min > max {
+                return Err(DiceError::MinAboveMax);
+            }
+        }
+        if clamp.min.into_iter().chain(clamp.max).any(|bound| bound as usize > TOTAL_LIMIT) {
+            return Err(DiceError::LimitExceeded { what: "Min/max" });
+        }
+        if let Some(count) = keep.count() {
+            if count == 0 || count >= coefficient {
+                return Err(DiceError::KeepCount);
+            }
+        }
+        if keep == Keep::Middle && (coefficient < 3 || coefficient % 2 == 0) {
+            return Err(DiceError::KeepMiddle);
+        }
+        if code != Code::Normal && negative {
+            return Err(DiceError::NotAllowed { what: "subtract the dice" });
         }
         Ok(())
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn parse(input: &str) -> Result<Vec<Command>, DiceError> { // the commands the prompt would throw for the input
+            generate_dice(input.to_string(), &ThrowOptions::default()).map(|(_, command_log)| command_log)
+        }
+
+        fn command(input: &str) -> Command { // a single command that has to read
+            let mut command_log = parse(input).unwrap_or_else(|error| panic!("'{input}' should parse: {error}"));
+            assert_eq!(command_log.len(), 1, "'{input}' should be one command");
+            command_log.remove(0)
+        }
+
+        #[test]
+        fn keep_rules() {
+            assert!(matches!(get_keep("4d6kh3"), Some((Keep::Highest(3), rest)) if rest == "4d6"));
+            assert!(matches!(get_keep("2d20kl1"), Some((Keep::Lowest(1), _))));
+            assert!(matches!(get_keep("5d6dh2"), Some((Keep::DropHighest(2), _))));
+            assert!(matches!(get_keep("4d6dl"), Some((Keep::DropLowest(1), _)))); // no count means 1
+            assert!(matches!(get_keep("2d6"), Some((Keep::All, rest)) if rest == "2d6"));
+            assert!(get_keep("4d6kh3dl1").is_none()); // one rule per command
+            assert!(parse("4d6kh4").is_err()); // has to leave something out
+            assert!(parse("4d6kh0").is_err());
+        }
+
+        #[test]
+        fn keep_kept() {
+            assert_eq!(Keep::Highest(2).kept(&[3, 6, 2, 5]), [false, true, false, true]);
+            assert_eq!(Keep::Lowest(1).kept(&[4, 2, 2]), [false, true, false]); // ties keep the first die
+            assert_eq!(Keep::DropLowest(1).kept(&[4, 2, 2]), [true, false, true]);
+            assert_eq!(Keep::DropHighest(1).kept(&[6, 3, 6]), [true, true, false]);
+            assert_eq!(Keep::All.kept(&[1, 2]), [true, true]);
+            assert_eq!(Keep::Highest(3).total(&[3, 6, 2, 5]), 14);
+            assert_eq!(command("4d6kh3").total(&[3, 6, 2, 5]), 14);
+        }
+
+        #[test]
+        fn rerolls() {
+            assert!(matches!(get_reroll("2d6r1"), Some((Reroll::Always(1), rest)) if rest == "2d6"));
+            assert!(matches!(get_reroll("2d6ro1"), Some((Reroll::Once(1), rest)) if rest == "2d6"));
+            assert!(get_reroll("2d6r").is_none()); // the face has to be there
+            assert!(get_reroll("2d6r1r2").is_none());
+            assert_eq!(parse("d6r7").err(), Some(DiceError::NotOnDie { what: "Reroll face" }));
+            assert_eq!(parse("d1r1").err(), Some(DiceError::Endless { what: "reroll" }));
+        }
+
+        #[test]
+        fn fudge_dice() {
+            let fudge = command("4dF");
+            assert!(fudge.kind == D::Fudge);
+            assert_eq!([1, 2, 3].map(|face| D::Fudge.score(face)), [-1, 0, 1]);
+            assert_eq!(fudge.total(&[-1, 0, 1, 1]), 1);
+            assert!(parse("4dF!").is_err());
+        }
+
+        #[test]
+        fn prefix_word_boundaries() {
+            assert!(get_options("skill d20").unwrap().0.skill_shot);
+            assert!(!get_options("skillful d20").unwrap().0.skill_shot);
+            assert!(get_options("lock 2 4d6").unwrap().0.rerolls.is_some());
+            assert!(get_options("lockpick d20").unwrap().0.rerolls.is_none());
+            assert!(!get_options("bursty d20").unwrap().0.burst);
+            assert!(get_options("BURST d20, d6").unwrap().0.burst); // still any case
+            assert_eq!(get_options("timed 10s d20").unwrap().0.time_limit, Some(10));
+        }
+
+        #[test]
+        fn success_pools() {
+            assert_eq!(command("4d6>=5").total(&[5, 6, 1, 2]), 2); // successes, not the sum
+            assert_eq!(command("4d6>4").total(&[5, 6, 1, 2]), 2);
+            assert_eq!(command("4d6<3").total(&[1, 2, 3, 6]), 2);
+            assert!(parse("d6>=5<2").is_err()); // one target per command
+            assert_eq!(parse("d6>65535").err(), Some(DiceError::Malformed { part: "Target number" })); // too big to even read
+        }
+
+        #[test]
+        fn targets_on_the_die() {
+            for impossible in ["d6<1", "d6>=7", "d6>6", "d6>=0", "4dF>1", "d6>65534", "d6>=65535", "4dF>=65535"] {
+                assert_eq!(parse(impossible).err(), Some(DiceError::NotOnDie { what: "Success target" }), "{impossible}");
+            }
+            for possible in ["5d6>=6", "5d6<2", "4dF>=1", "3dc>=1", "sr 5d6"] {
+                assert!(parse(possible).is_ok(), "{possible}");
+            }
+        }
+
+        #[test]
+        fn factors() {
+            assert!(command("2d6*10").factor == Factor::Multiply(10));
+            assert!(command("8d6/2").factor == Factor::Divide(2)); // a constant, so not another command
+            assert_eq!(Factor::Divide(2).apply(7), 3);
+            assert_eq!(command("2d6*10").value(&[3, 4]), 70);
+            assert_eq!(parse("d6/0").err(), Some(DiceError::DivideByZero));
+            assert!(get_factor("2d6*2/2").is_none());
+            assert!(parse("d99*99, d99*99, d99*99").is_ok());
+            assert_eq!(parse("d99*99, d99*99, d99*99, d99*99").err(), Some(DiceError::LimitExceeded { what: "Total" })); // the sum would pass what it can hold
+            assert_eq!(command("4d6*10-3").reach(), 243);
+            assert_eq!(command("d6min20+1").reach(), 21);
+            assert_eq!(command("3d6max5").reach(), 5);
+        }
+
+        #[test]
+        fn subtracted_dice() {
+            let command_log = parse("2d8-1d4").unwrap();
+            assert_eq!(command_log.len(), 2);
+            assert!(!command_log[0].negative && command_log[1].negative);
+            assert!(command_log[1].kind == D::Four);
+            assert_eq!(command_log[1].value(&[3]), -3);
+            assert_eq!(command("d20-1").modifier(), -1); // a constant stays a modifier
+            assert!(parse("-adv d20").is_err());
+        }
+
+        #[test]
+        fn keep_middle() {
+            assert!(command("mid 3d20").keep == Keep::Middle);
+            assert_eq!(Keep::Middle.kept(&[5, 1, 3]), [false, false, true]);
+            assert!(parse("mid 4d20").is_err()); // needs a middle die
+            assert!(parse("mid d20").is_err());
+            assert!(parse("mid 3d20kh1").is_err());
+        }
+
+        #[test]
+        fn commas_in_labels() {
+            assert_eq!(split_commands("d20 # a, b"), ["d20 # a, b"]);
+            assert_eq!(split_commands("d20 \"a, b\", d6"), ["d20 \"a, b\"", " d6"]);
+            assert_eq!(split_commands("d20 # hit, 2d6 # dmg"), ["d20 # hit", " 2d6 # dmg"]); // more dice start a new command
+            assert_eq!(split_commands("2d6/d8, d4-1d4"), ["2d6", "d8", " d4", "-1d4"]);
+            assert_eq!(command("d20 # a, b").label.as_deref(), Some("a, b"));
+            assert_eq!(command("2d6+3 \"Fire\"").label.as_deref(), Some("Fire")); // the label keeps its case
+            assert_eq!(split_commands("2d6 # fire, adv d20, 3x(d4), coin"), ["2d6 # fire", " adv d20", " 3x(d4)", " coin"]);
+            assert_eq!(split_commands("d20 # hit, dmg later"), ["d20 # hit, dmg later"]); // a word starting with 'd' isn't dice
+            assert_eq!(parse("d20 # a label much too long to fit").err(), Some(DiceError::LabelTooLong { limit: 24 }));
+            assert_eq!(parse("d20 \"unclosed").err(), Some(DiceError::Malformed { part: "Label" }));
+        }
+
+        #[test]
+        fn prefix_and_flag_errors() {
+            let options = |input: &str| get_options(input).err();
+            assert_eq!(options("timed 0s 3d6"), Some(DiceError::OutOfRange { what: "Time limit (seconds)", min: 1, max: 99 }));
+            assert_eq!(options("skill timed 5s d20"), Some(DiceError::Conflict { first: "A skill shot", second: "a time limit" }));
+            assert_eq!(options("horde 21 atk d20 ac 15 dmg d6"), Some(DiceError::OutOfRange { what: "Horde attackers", min: 1, max: 20 }));
+            assert_eq!(options("d20 --loud"), Some(DiceError::UnknownFlag { flag: String::from("--loud") }));
+            assert_eq!(options("d20 --label"), Some(DiceError::Needs { what: "--label", needs: "some text, e.g. '--label \"trap dmg\"'" }));
+            assert_eq!(parse("0d6").err(), Some(DiceError::ZeroCoefficient));
+            assert_eq!(parse("4d6kh4").err(), Some(DiceError::KeepCount));
+            assert_eq!(parse("adv d20r1").err(), Some(DiceError::NotAllowed { what: "reroll dice" }));
+        }
+
+        #[test]
+        fn non_ascii_labels() {
+            assert_eq!(command("d20 # café").label.as_deref(), Some("café"));
+            assert_eq!(command("2d6 \"épée\"").label.as_deref(), Some("épée"));
+            assert_eq!(split_commands("d20 # café, d6 \"épée\"/d8"), ["d20 # café", " d6 \"épée\"", "d8"]);
+            assert_eq!(split_commands("d20 # naïve, déjà vu"), ["d20 # naïve, déjà vu"]);
+        }
+
+        #[test]
+        fn named_modifiers() {
+            let modifiers = get_modifiers("+3(str) +2(prof) -1(cover)").unwrap();
+            let listed: Vec<String> = modifiers.iter().map(|modifier| modifier.to_string()).collect();
+            assert_eq!(listed, ["+3(str)", "+2(prof)", "-1(cover)"]);
+            let named = command("d20 +3(str) +2(prof) -1(cover)");
+            assert_eq!(named.modifier(), 4);
+            assert!(named.itemized());
+            assert!(!command("d20+4").itemized());
+            assert!(get_modifiers("+3()").is_none());
+            assert!(get_modifiers("+3(str").is_none());
+        }
+
+        #[test]
+        fn clamps() {
+            let clamped = command("3d6min8");
+            assert!(clamped.clamp == Clamp { min: Some(8), max: None });
+            assert_eq!(clamped.value(&[1, 2, 1]), 8);
+            assert_eq!(command("d20max15").value(&[20]), 15);
+            assert_eq!(command("3d6 min 4 max 15").value(&[6, 6, 6]), 15);
+            assert_eq!(parse("3d6min9max8").err(), Some(DiceError::MinAboveMax));
+        }
+
+        #[test]
+        fn botches() {
+            assert_eq!(command("4d10>=6 botch").total(&[6, 9, 1, 2]), 1); // a 1 cancels a success
+            assert_eq!(command("3d10>=6 botch").total(&[1, 1, 2]), 0); // but never below none
+            assert_eq!(command("3d10>=6 botch").ones(&[1, 1, 2]), 2);
+            assert!(parse("3d10 botch").is_err()); // needs a target
+            assert!(parse("3d10<=4 botch").is_err());
+        }
+
+        #[test]
+        fn repeats() {
+            assert!(matches!(get_repeat("3x(2d6+1)"), Some((3, inside)) if inside == "2d6+1"));
+            assert!(matches!(get_repeat("2d6"), Some((1, input)) if input == "2d6"));
+            assert!(get_repeat("0x(d6)").is_none());
+            assert!(get_repeat("21x(d6)").is_none());
+            assert!(get_repeat("3x(d6").is_none()); // brackets must be closed
+            let labels: Vec<Option<String>> = parse("3x(2d6+1)").unwrap().into_iter().map(|command| command.label).collect();
+            assert_eq!(labels, [Some("#1".to_string()), Some("#2".to_string()), Some("#3".to_string())]);
+            assert!(parse("3x(d99*99)").is_ok());
+            assert_eq!(parse("9x(d99*99)").err(), Some(DiceError::LimitExceeded { what: "Repeat" }));
+        }
+
+        #[test]
+        fn zero_sides() {
+            assert!(parse("d0").is_err());
+            assert!(parse("3d0+2").is_err());
+            assert!(parse("d1").is_ok());
+            let mut built = command("3d6"); // as the builder could make it, without reading any text
+            built.kind = D::Custom(0);
+            assert_eq!(validate(&built).err(), Some(DiceError::NoSides));
+        }
+
+        #[test]
+        fn written_back() {
+            for input in ["adv d20+5", "adv3 d20", "disadv d20-1", "wild d8+2", "d100", "mid 3d20", "sr 12d6", "4d6kh3", "2d6r1!", "8d10>=8 botch", "3d6*2min8+1(str)", "3 coins"] {
+                let read = command(input);
+                assert!(command(&read.to_string()) == read, "'{input}' was written as '{read}'");
+            }
+            assert_eq!(command("adv d20+5").to_string(), "adv 1d20+5");
+            assert_eq!(command("disadv d20").to_string(), "disadv 1d20");
+        }
+
+        #[test]
+        fn large_dice() {
+            assert!(command("d120").kind == D::Custom(120));
+            assert!(command("2d999").kind == D::Custom(999));
+            assert!(command("d1000").kind == D::PercentHundreds); // still percentile
+            assert!(command("d9999").kind == D::Custom(9999));
+            assert_eq!(parse("d10000").err(), Some(DiceError::LimitExceeded { what: "Dice total" }));
+            assert_eq!(parse("11d999").err(), Some(DiceError::LimitExceeded { what: "Dice total" }));
+            assert!(parse("99d99").is_ok());
+        }
+    }
 }
\ No newline at end of file
@@ -1,41 +1,841 @@
-use std::io::{stdout, Write};
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{stdin, stdout, Write};
+use std::path::PathBuf;
+use std::process;
 
+use dice::{D, Thrown};
+use dice::error::DiceError;
+use dice::events::Events;
+use dice::headless::RollResult;
+use dice::history::Entry;
+use dice::narration::Narration;
 use dice::input_handling;
+use dice::rng::Backend;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 
 // command line dice roller
 
-fn main() {
-    print!("\nEnter command (or 'help' / 'quit'):");
-    loop {
+enum Roll { // how a single roll went, as far as the prompt loop cares
+    Done(Entry),
+    Invalid,
+    Exit(Entry), // thrown (and recorded), then Esc was pressed to leave the program
+}
+
+const KEYWORDS: [&str; 31] = [ // what Tab completes at the prompt, besides macros, aliases, and recent rolls
+    "adv", "adv3", "disadv", "mid", "wild", "sr", "coin", "flip", "timed", "lock", "skill",
+    "split", "burst", "genchar", "horde", "pointbuy", "record macro", "alias", "profile", "history", "replay", "export", "usage", "sandbox", "die", "roll with", "bench", "unbench", "help", "quit", "exit",
+];
+const RECENT_LIMIT: usize = 20; // rolls remembered for Tab
+const RESERVED: [&str; 18] = ["help", "quit", "exit", "stop", "pointbuy", "record", "alias", "profile", "history", "replay", "export", "usage", "sandbox", "burst", "die", "roll", "bench", "unbench"]; // words an alias or macro can't take over
+
+struct NamedDie { // a die of the user's own, with its own seed and lifetime stats -- for the superstitious
+    kind: String, // as written, e.g. "d20"
+    seed: u64, // what its next throw starts from, moved on after every throw
+    rolls: u32,
+    ones: u32,
+    sum: i64, // of what the faces scored, e.g. -1 for a dF's minus
+    benched: bool, // retired: kept with its stats, but not thrown
+}
+
+impl NamedDie {
+    fn sides(&self) -> u16 {
+        input_handling::get_kind(&self.kind).map(|kind| kind.as_number()).unwrap_or(1)
+    }
+
+    fn record(&mut self, face: u16) { // counts a throw that landed on the face, and moves the seed on for the next
+        self.seed = dice::rng::mix(self.seed);
+        self.rolls += 1;
+        self.ones += (face == 1) as u32;
+        self.sum += input_handling::get_kind(&self.kind).map_or(face as i16, |kind| kind.score(face)) as i64;
+    }
+
+    fn unlucky(&self) -> bool { // whether it's rolled 1s at twice the rate it should, over enough of them to tell
+        const MIN_ONES: u32 = 3;
+        self.ones >= MIN_ONES && self.ones as u64 * self.sides() as u64 >= 2 * self.rolls as u64
+    }
+}
+
+#[derive(Clone, Default)]
+struct Settings { // from the config file, then overridden by flags
+    no_anim: bool,
+    plain: bool, // line by line with no cursor movement, for dumb terminals and screen readers
+    csv: Option<PathBuf>, // every throw is added to this file as a row
+    svg: Option<PathBuf>, // the latest throw's results are drawn into this file
+    quiet: bool, // only the number each command comes to, one per line, for shell scripts
+    verbose: bool, // every die and step before the result, for reading without the graph
+    history: Option<PathBuf>, // where every throw is recorded, in the profile's folder
+    seed: Option<u64>, // makes the whole session's dice come out the same every time, for demos and tests
+    rng: Option<Backend>, // the generator the dice draw from, the standard one if not set
+    sandbox: bool, // practice rolls: shown as usual but kept out of the history, CSV, usage, and export
+    watch_open: Option<String>, // what marks an expression in a watched file, "[[" if not set
+    watch_close: Option<String>, // and its end, "]]" if not set
+    narration: Narration, // lines printed under a throw's results, keyed to how it went
+}
+
+struct Session { // everything the prompt keeps between commands, and the keyboard it reads them from
+    flags: Settings, // as given on the command line, to lay over a profile's settings when switching
+    settings: Settings,
+    profile: Option<String>,
+    aliases: HashMap<String, String>,
+    macros: HashMap<String, Vec<String>>,
+    named: HashMap<String, NamedDie>,
+    recording: Option<(String, Vec<String>)>, // the macro being recorded, if any
+    recent: Vec<String>, // rolls made this session, most recent first
+    entries: Vec<Entry>, // every throw this session, for exporting
+    next_seed: Box<dyn FnMut() -> Option<u64>>,
+    events: Events,
+}
+
+impl Session {
+    fn run(&mut self) { // carries out commands as they're typed, until one leaves the program
+        print!("\nEnter command (or 'help' / 'quit'):");
+        loop {
+            let input = self.read();
+            if !self.command(input) {
+                break;
+            }
+        }
+    }
 
-        // get input
-        print!("\nRoll: ");
+    fn read(&mut self) -> String { // the next line typed at the prompt
+        let prompt = if self.settings.sandbox { "Sandbox roll: " } else { "Roll: " };
+        print!("\n{prompt}");
         stdout().flush().unwrap();
-        let input = dice::get_input();
+        let mut words: Vec<String> = KEYWORDS.iter().map(|word| word.to_string()).collect();
+        words.extend(self.macros.keys().chain(self.aliases.keys()).cloned());
+        match self.settings.plain {
+            true => self.events.line(),
+            false => dice::get_completed_input(prompt, &words, &self.recent, &mut self.events),
+        }
+    }
+
+    fn command(&mut self, input: String) -> bool { // carries out a line of input, returning false to leave the program
         match &input.trim().to_lowercase()[..] {
             "help" => {
                 dice::help();
-                continue;
+                return true;
             },
-            "quit" | "exit" => break,
-            _ => ()
-        }
-        
-        // roll
-        match input_handling::generate_dice(input) {
-            Ok((code, dice, modifiers, log)) => {
-                match dice::throw(code, dice, modifiers, log) {
-                    Some(result) => {
-                        println!("Result: {result}");
-                        continue;
+            "quit" | "exit" => return false,
+            command if command.starts_with("pointbuy") => { // not a roll, just a check on bought scores
+                match dice::point_buy(&command["pointbuy".len()..]) {
+                    Ok(result) => println!("Point buy: {result}"),
+                    Err(error) => println!("{error}"),
+                }
+                return true;
+            },
+            command if command.starts_with("record macro") => {
+                let name = command["record macro".len()..].trim();
+                if name.is_empty() || name.contains(char::is_whitespace) || RESERVED.contains(&name) {
+                    println!("Macro name must be a single word, e.g. 'record macro attack'");
+                    return true;
+                }
+                println!("Recording macro '{name}' -- enter 'stop' when done");
+                self.recording = Some((name.to_string(), Vec::new()));
+                return true;
+            },
+            "stop" => {
+                match self.recording.take() {
+                    Some((_, inputs)) if inputs.is_empty() => println!("Nothing recorded"),
+                    Some((name, inputs)) => {
+                        let plural = if inputs.len() == 1 { "" } else { "s" };
+                        println!("Recorded macro '{name}' ({} roll{plural}) -- enter '{name}' to play it", inputs.len());
+                        self.macros.insert(name, inputs);
+                        save_macros(self.profile.as_deref(), &self.aliases, &self.macros);
+                    },
+                    None => println!("Not recording"),
+                }
+                return true;
+            },
+            command if command == "history" || command.starts_with("history ") => {
+                const DEFAULT_COUNT: usize = 10;
+                let count = match command["history".len()..].trim() {
+                    "" => DEFAULT_COUNT,
+                    count => match count.parse::<usize>() {
+                        Ok(n) if n > 0 => n,
+                        _ => {
+                            println!("History takes a number of rolls, e.g. 'history 20'");
+                            return true;
+                        },
+                    },
+                };
+                let rolls = self.settings.history.as_deref().map(|path| dice::history::recent(path, count)).unwrap_or_default();
+                if rolls.is_empty() {
+                    println!("No rolls yet");
+                }
+                for (n, roll) in rolls.iter().enumerate() { // numbered back from the latest, for 'replay'
+                    println!("{:>3}  {:>8}  {roll}", rolls.len() - n, dice::history::ago(roll.time));
+                }
+                return true;
+            },
+            command if command == "replay" || command.starts_with("replay ") => { // the same throw again, from its seed
+                let back = match command["replay".len()..].trim() {
+                    "" => 1,
+                    n => match n.parse::<usize>() {
+                        Ok(n) if n > 0 => n,
+                        _ => {
+                            println!("Replay takes a roll's number from 'history', e.g. 'replay 3'");
+                            return true;
+                        },
+                    },
+                };
+                let rolls = self.settings.history.as_deref().map(|path| dice::history::recent(path, back)).unwrap_or_default();
+                match rolls.first() {
+                    Some(_) if rolls.len() < back => println!("Only {} rolls in the history", rolls.len()),
+                    Some(Entry { table: Some(true), .. }) if self.settings.plain => println!("That roll was thrown on the table, so it can only be replayed where the table can be drawn"),
+                    Some(Entry { command, seed: Some(seed), table, .. }) => {
+                        println!("Replaying {command}");
+                        let settings = Settings { plain: self.settings.plain || *table == Some(false), ..self.settings.clone() }; // the same seed only lands the same faces the same way
+                        match roll(command, &settings, Some(*seed), &mut self.events) {
+                            Roll::Done(entry) if !settings.sandbox => self.entries.push(entry),
+                            Roll::Done(_) | Roll::Invalid => (),
+                            Roll::Exit(_) => return false,
+                        }
+                    },
+                    Some(_) => println!("That roll was made before seeds were kept, so it can't be replayed"),
+                    None => println!("No rolls yet"),
+                }
+                return true;
+            },
+            "sandbox" => {
+                self.settings.sandbox = !self.settings.sandbox;
+                match self.settings.sandbox {
+                    true => println!("Sandbox on -- rolls won't be kept until you enter 'sandbox' again"),
+                    false => println!("Sandbox off -- rolls are kept again"),
+                }
+                return true;
+            },
+            "usage" => { // from the history file only -- nothing leaves this machine
+                let rolls = self.settings.history.as_deref().map(|path| dice::history::recent(path, usize::MAX)).unwrap_or_default();
+                for line in dice::history::usage(&rolls) {
+                    println!("{line}");
+                }
+                let mut unused: Vec<&String> = self.aliases.iter() // aliases are expanded before they're recorded, so look for their rolls
+                    .filter(|(_, roll)| !rolls.iter().any(|entry| entry.command.to_lowercase().contains(&roll.to_lowercase())))
+                    .map(|(name, _)| name)
+                    .collect();
+                unused.sort();
+                if !rolls.is_empty() && !unused.is_empty() {
+                    println!("Aliases never rolled: {}", unused.iter().map(|name| name.as_str()).collect::<Vec<&str>>().join(", "));
+                }
+                return true;
+            },
+            command if command.starts_with("export") => {
+                let path = input.trim()["export".len()..].trim(); // case is kept for the file name
+                if path.is_empty() {
+                    println!("Export needs a file, e.g. 'export session.txt'");
+                    return true;
+                }
+                match dice::history::export(&PathBuf::from(path), &self.entries) {
+                    Ok(()) => println!("Exported {} rolls to {path}", self.entries.len()),
+                    Err(error) => println!("{error}"),
+                }
+                return true;
+            },
+            "profile" => {
+                println!("Profile: {}", self.profile.as_deref().unwrap_or("default"));
+                return true;
+            },
+            command if command.starts_with("profile ") => { // switch games: everything saved comes from the other profile
+                let name = command["profile".len()..].trim();
+                if !is_profile_name(name) {
+                    println!("Profile name must be a single word, e.g. 'profile campaign'");
+                    return true;
+                }
+                self.profile = (name != "default").then(|| name.to_string());
+                (self.aliases, self.macros) = load_macros(self.profile.as_deref());
+                self.named = load_named_dice(self.profile.as_deref());
+                self.settings = Settings { sandbox: self.settings.sandbox, ..load_settings(self.profile.as_deref(), self.flags.clone()) };
+                self.recording = None;
+                println!("Switched to profile '{name}'");
+                return true;
+            },
+            "alias" => {
+                let mut names: Vec<_> = self.aliases.iter().collect();
+                names.sort();
+                for (name, roll) in names {
+                    println!("{name} = {roll}");
+                }
+                return true;
+            },
+            "die" => {
+                let mut names: Vec<_> = self.named.iter().collect();
+                names.sort_by(|a, b| a.0.cmp(b.0));
+                if names.is_empty() {
+                    println!("No dice of your own yet -- make one with 'die lucky = d20'");
+                }
+                for (name, die) in names {
+                    let average = if die.rolls == 0 { 0.0 } else { die.sum as f64 / die.rolls as f64 };
+                    let plural = |count: u32| if count == 1 { "" } else { "s" };
+                    println!("{name} ({}): {} roll{}, {} one{}, average {average:.1}{}", die.kind, die.rolls, plural(die.rolls), die.ones, plural(die.ones), if die.benched { " -- benched" } else { "" });
+                }
+                return true;
+            },
+            command if command.starts_with("die ") => {
+                let Some((name, kind)) = command["die".len()..].split_once('=') else {
+                    println!("A die needs a name and a kind, e.g. 'die lucky = d20'");
+                    return true;
+                };
+                let (name, kind) = (name.trim(), kind.trim());
+                if !is_profile_name(name) {
+                    println!("Die name must be a single word, e.g. 'die lucky = d20'");
+                    return true;
+                }
+                if kind.is_empty() { // 'die lucky =' forgets it
+                    match self.named.remove(name) {
+                        Some(_) => println!("Removed die '{name}'"),
+                        None => println!("No die named '{name}'"),
+                    }
+                } else {
+                    let kind = format!("d{}", kind.strip_prefix("1d").or(kind.strip_prefix('d')).unwrap_or(kind).trim()); // "d20", "1d20", and "20" are all one die
+                    match input_handling::get_kind(&kind) {
+                        Some(D::PercentTens | D::PercentHundreds) | None => {
+                            println!("A die of your own is a single die, e.g. 'die lucky = d20'");
+                            return true;
+                        },
+                        Some(_) => {
+                            self.named.insert(name.to_string(), NamedDie { kind: kind.clone(), seed: rand::random(), rolls: 0, ones: 0, sum: 0, benched: false });
+                            println!("Made '{name}', a {kind} of your own -- enter 'roll with {name}' to throw it");
+                        },
+                    }
+                }
+                save_named_dice(self.profile.as_deref(), &self.named);
+                return true;
+            },
+            command if command.starts_with("bench ") || command.starts_with("unbench ") => {
+                let (verb, name) = command.split_once(' ').unwrap_or_default();
+                let Some(die) = self.named.get_mut(name.trim()) else {
+                    println!("No die named '{}'", name.trim());
+                    return true;
+                };
+                die.benched = verb == "bench";
+                match die.benched {
+                    true => println!("Benched '{}' -- it keeps its stats but sits out", name.trim()),
+                    false => println!("'{}' is back in play", name.trim()),
+                }
+                save_named_dice(self.profile.as_deref(), &self.named);
+                return true;
+            },
+            command if command.starts_with("roll with ") => { // a die of the user's own, thrown from its own seed
+                let rest = input.trim()["roll with".len()..].trim(); // case is kept for labels
+                let end = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '-' && c != '_').unwrap_or(rest.len()); // the name runs up to the modifier or label, e.g. "lucky+5"
+                let (name, after) = (rest[..end].to_lowercase(), &rest[end..]);
+                let Some(die) = self.named.get_mut(&name) else {
+                    println!("No die named '{name}' -- make one with 'die {name} = d20'");
+                    return true;
+                };
+                if die.benched {
+                    println!("'{name}' is benched -- 'unbench {name}' to bring it back");
+                    return true;
+                }
+                let seed = if self.settings.sandbox { (self.next_seed)() } else { Some(die.seed) }; // practice throws leave the die alone
+                let (entry, exit) = match roll(&format!("1{}{after}", die.kind), &self.settings, seed, &mut self.events) {
+                    Roll::Done(entry) => (entry, false),
+                    Roll::Exit(entry) => (entry, true), // still counts, so the next session doesn't throw it again
+                    Roll::Invalid => return true,
+                };
+                if !self.settings.sandbox {
+                    let face = entry.dice.first().map(|(_, face)| *face).unwrap_or_default(); // the die itself, not anything it set off
+                    die.record(face);
+                    if face == 1 && die.unlucky() && !exit {
+                        println!("'{name}' has rolled {} ones in {} throws -- 'bench {name}' to retire it", die.ones, die.rolls);
+                    }
+                    save_named_dice(self.profile.as_deref(), &self.named);
+                    self.entries.push(entry);
+                }
+                return !exit;
+            },
+            command if command.starts_with("alias ") => {
+                let Some((name, _)) = command["alias".len()..].split_once('=') else {
+                    println!("Alias needs a name and a roll, e.g. 'alias attack = d20+7'");
+                    return true;
+                };
+                let name = name.trim();
+                let roll = input.split_once('=').map(|(_, roll)| roll.trim()).unwrap_or_default(); // case is kept for labels
+                if name.is_empty() || name.contains(char::is_whitespace) || RESERVED.contains(&name) {
+                    println!("Alias name must be a single word, e.g. 'alias attack = d20+7'");
+                    return true;
+                }
+                match roll.is_empty() { // 'alias attack =' forgets it
+                    true => {
+                        self.aliases.remove(name);
+                        println!("Removed alias '{name}'");
+                    },
+                    false => {
+                        self.aliases.insert(name.to_string(), roll.to_string());
+                        println!("Saved alias '{name}' -- enter '{name}' to roll {roll}");
                     },
-                    None => break,
                 }
+                save_macros(self.profile.as_deref(), &self.aliases, &self.macros);
+                return true;
+            },
+            _ => ()
+        }
+
+        // a macro name plays its rolls back in order, anything else is a single roll
+        let inputs = match self.macros.get(&input.trim().to_lowercase()) {
+            Some(inputs) => inputs.clone(),
+            None => vec![burst_file(&input).unwrap_or(input)],
+        };
+        let inputs: Vec<String> = inputs.iter().map(|input| expand_aliases(input, &self.aliases)).collect();
+        let mut answers = HashMap::new(); // each prompt is asked once per play
+        for input in inputs {
+            match roll(&fill_prompts(&input, &mut answers, &mut self.events), &self.settings, (self.next_seed)(), &mut self.events) {
+                Roll::Done(entry) => {
+                    if !self.settings.sandbox {
+                        self.entries.push(entry);
+                    }
+                    self.recent.retain(|roll| roll != input.trim());
+                    self.recent.insert(0, input.trim().to_string());
+                    self.recent.truncate(RECENT_LIMIT);
+                    if let Some((_, recorded)) = self.recording.as_mut() {
+                        recorded.push(input.trim().to_string()); // prompts are kept, so the macro asks again
+                    }
+                },
+                Roll::Invalid => break, // the rest of a macro waits for a fix
+                Roll::Exit(_) => return false,
+            }
+        }
+        true
+    }
+}
+
+fn fail(message: &str) -> ! { // every flag and usage error goes the same way: to stderr, exiting with 2 (0 and 1 are --check's made and missed)
+    eprintln!("{message}");
+    process::exit(2);
+}
+
+fn main() {
+
+    // flags can go anywhere, the rest is the mode
+    let mut flags = Settings {
+        plain: env::var("TERM").is_ok_and(|term| term == "dumb"), // the table can't be drawn, so roll as if piped
+        ..Settings::default()
+    };
+    let mut profile: Option<String> = None; // a named set of config and macros, for running more than one game
+    let mut check: Option<i16> = None; // a DC for a one-shot roll to meet, reported by the exit code
+    let mut args: Vec<String> = Vec::new();
+    let mut given = env::args().skip(1);
+    while let Some(arg) = given.next() {
+        match arg.as_str() {
+            "--no-anim" => flags.no_anim = true,
+            "--quiet" => flags.quiet = true,
+            "--verbose" => flags.verbose = true,
+            "--check" => match given.next().map(|dc| dc.parse::<i16>()) {
+                Some(Ok(dc)) => check = Some(dc),
+                _ => fail("Check needs a DC, e.g. '--check 15 d20+5'"),
             },
-            Err(error) => {
-                println!("{error}");
-                continue;
+            "--seed" => match given.next().map(|seed| seed.parse::<u64>()) {
+                Some(Ok(seed)) => flags.seed = Some(seed),
+                _ => fail("Seed must be a whole number, e.g. '--seed 42'"),
             },
+            "--rng" => match given.next().as_deref().and_then(Backend::parse) {
+                Some(backend) => flags.rng = Some(backend),
+                None => fail("RNG must be 'standard', 'os', or 'fast', e.g. '--rng fast'"),
+            },
+            "--csv" => match given.next() {
+                Some(path) => flags.csv = Some(PathBuf::from(path)),
+                None => fail("CSV needs a file, e.g. '--csv rolls.csv'"),
+            },
+            "--svg" => match given.next() {
+                Some(path) => flags.svg = Some(PathBuf::from(path)),
+                None => fail("SVG needs a file, e.g. '--svg roll.svg'"),
+            },
+            "--profile" => match given.next() {
+                Some(name) if is_profile_name(&name) => profile = Some(name),
+                _ => fail("Profile name must be a single word, e.g. '--profile campaign'"),
+            },
+            _ => args.push(arg),
+        }
+    }
+    if check.is_some() && matches!(args.first().map(String::as_str), None | Some("--file" | "watch" | "tutorial" | "--rpc")) { // the DC is read off a single throw's total
+        fail("--check is for a single roll, e.g. 'dice --check 15 d20+5'");
+    }
+    let (aliases, macros) = load_macros(profile.as_deref()); // saved in the macros file, by name
+    let named = load_named_dice(profile.as_deref()); // dice of the user's own, by name
+    let settings = load_settings(profile.as_deref(), flags.clone());
+
+    match &args[..] {
+        [flag, path] if flag == "--file" => { // a prepared list of rolls, e.g. for an encounter
+            match fs::read_to_string(path) {
+                Ok(contents) => run_script(contents.lines().map(String::from), &aliases, &macros, &settings),
+                Err(_) => fail(&format!("Could not read {path}")),
+            }
+            return;
+        },
+        [mode, path] if mode == "watch" => { // play-by-post notes
+            println!("Watching {path} -- press Ctrl-C to stop");
+            let open = settings.watch_open.as_deref().unwrap_or("[[");
+            let close = settings.watch_close.as_deref().unwrap_or("]]");
+            if let Err(error) = dice::watch::watch(path, open, close) {
+                fail(error);
+            }
+            return;
+        },
+        [mode] if mode == "tutorial" => { // for new players
+            tutorial(&settings);
+            return;
+        },
+        [flag] if flag == "--rpc" => { // for editor plugins
+            dice::rpc::serve();
+            return;
+        },
+        [] => (),
+        [flag, ..] if flag.starts_with("--") => fail("Usage: dice [--no-anim] [--quiet | --verbose] [--csv <path>] [--svg <path>] [--check <dc>] [--seed <n>] [--rng <standard|os|fast>] [--profile <name>] [--rpc | --file <path> | watch <path> | tutorial | <roll>]"),
+        roll => { // one-shot, e.g. 'dice adv d20+5'
+            let input = expand_aliases(&roll.join(" "), &aliases);
+            match (roll_plain(&input, &settings, seeds(&settings)()), check) {
+                (Ok((result, entry)), Some(dc)) => { // for scripts: exit 0 if the DC was met, 1 if not
+                    println!("{result}");
+                    let made = entry.total >= dc;
+                    if !settings.quiet {
+                        println!("{} DC {dc}", if made { "Made" } else { "Missed" });
+                    }
+                    process::exit(if made { 0 } else { 1 });
+                },
+                (Ok((result, _)), None) => println!("{result}"),
+                (Err(error), _) => fail(&error.to_string()),
+            }
+            return;
+        },
+    }
+    if !termion::is_tty(&stdin()) { // piped in, so there's nobody to animate for
+        run_script(stdin().lines().map_while(Result::ok), &aliases, &macros, &settings);
+        return;
+    }
+    let mut session = Session {
+        next_seed: Box::new(seeds(&settings)),
+        flags,
+        settings,
+        profile,
+        aliases,
+        macros,
+        named,
+        recording: None,
+        recent: Vec::new(),
+        entries: Vec::new(),
+        events: Events::new(), // the keyboard, from here on
+    };
+    session.run();
+}
+
+fn tutorial(settings: &Settings) { // walks through the basics one roll at a time. each step's dice come from a fixed seed so everyone sees the same thing, and none of it is kept
+    const SEED: u64 = 2024;
+    const STEPS: [(&str, &str); 5] = [ // what to read, then what to try
+        ("Rolls are written as how many dice, 'd', the sides, then any modifier.\n\
+          '2d6+3' throws two six-sided dice and adds 3. When they stop, press any key\n\
+          for the working, then 'r' to come back here.", "2d6+3"),
+        ("Put 'adv' in front to throw twice and keep the better total ('disadv' keeps\n\
+          the worse). The throw that doesn't count is greyed out.", "adv d20+5"),
+        ("'d100' (or 'd%') throws a tens die and a ones die. 00 and 0 together make 100.", "d100"),
+        ("A pool counts the dice that reach a target instead of adding them up.\n\
+          '6d10>=7' counts every die showing 7 or more.", "6d10>=7"),
+        ("After a throw, 't' switches between the dice and the working, 'r' goes back to\n\
+          the prompt, and Esc quits. In lock mode the number keys hold dice and 'e'\n\
+          throws the rest again -- lock a die or two, then press 'e'.", "lock 5d6"),
+    ];
+    if !termion::is_tty(&stdin()) {
+        eprintln!("The tutorial needs a terminal");
+        return;
+    }
+    let settings = Settings { csv: None, svg: None, history: None, rng: None, ..settings.clone() }; // practice rolls stay out of the records
+    let mut events = Events::new();
+    println!("\nWelcome! Each step shows a roll to try -- press Enter to use it, or type your own.\nEnter 'quit' to stop at any time.");
+    let steps = if settings.plain { &STEPS[..STEPS.len() - 1] } else { &STEPS[..] }; // the last step is about the table's keys
+    let mut step = 0;
+    while let Some((text, example)) = steps.get(step) {
+        println!("\n{}/{}. {text}", step + 1, steps.len());
+        print!("Try: {example}\nRoll: ");
+        stdout().flush().unwrap();
+        let input = events.line();
+        let input = match input.trim() {
+            "" => example.to_string(),
+            "quit" | "exit" => return,
+            typed => typed.to_string(),
+        };
+        match roll(&input, &settings, Some(SEED + step as u64), &mut events) {
+            Roll::Done(_) => step += 1,
+            Roll::Invalid => println!("Not quite -- try '{example}'"),
+            Roll::Exit(_) => return,
+        }
+    }
+    println!("\nThat's the basics. Enter 'help' at the prompt for everything else.");
+}
+
+fn run_script(lines: impl Iterator<Item = String>, aliases: &HashMap<String, String>, macros: &HashMap<String, Vec<String>>, settings: &Settings) { // rolls each line as it comes and prints plain results, one line each. blank lines and comments ('#' or '//' to start a line) are skipped
+    let mut next_seed = seeds(settings);
+    for (number, line) in lines.enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with("//") {
+            continue;
+        }
+        let inputs = match macros.get(&trimmed.to_lowercase()) {
+            Some(inputs) => inputs.clone(),
+            None => vec![burst_file(&line).unwrap_or(line)],
+        };
+        for input in inputs {
+            match roll_plain(&expand_aliases(&input, aliases), settings, next_seed()) {
+                Ok((result, _)) => println!("{result}"),
+                Err(error) => eprintln!("Line {}: {error}", number + 1),
+            }
+        }
+    }
+}
+
+fn seeds(settings: &Settings) -> impl FnMut() -> Option<u64> { // each throw's seed, drawn in turn from --seed if it was given, or else left to the throw
+    let mut rng = settings.seed.map(StdRng::seed_from_u64);
+    move || rng.as_mut().map(|rng| rng.gen())
+}
+
+fn roll_headless(input: &str, settings: &Settings, seed: Option<u64>) -> Roll { // throws without the table, printing the result
+    match roll_plain(input, settings, seed) {
+        Ok((result, entry)) => {
+            println!("Result: {result}");
+            Roll::Done(entry)
+        },
+        Err(error) => {
+            println!("{error}");
+            Roll::Invalid
+        },
+    }
+}
+
+fn roll_plain(input: &str, settings: &Settings, seed: Option<u64>) -> Result<(String, Entry), DiceError> { // parses and throws one line of input without the table (from the given seed, or a new one), returning what to print and the throw
+    let (mut options, parsed) = input_handling::get_options(input)?;
+    let seed = options.seed.or(seed).unwrap_or_else(rand::random); // a '--seed' on the roll itself wins
+    options.seed = Some(seed);
+    options.rng = settings.rng.unwrap_or_default();
+    let (_, log) = input_handling::generate_dice(parsed, &options)?;
+    let commands = dice::headless::roll(&log, &options)?;
+    let result = dice::headless::finish(&options, commands);
+    log_csv(settings, input, &result.line, result.total);
+    log_svg(settings, input, &result);
+    let entry = Entry::new(input, &result.faces(), &result.rerolled(), result.total, seed, false);
+    log_history(settings, &entry);
+    let narrated = settings.narration.narrate(&result.commands, result.total).map(|line| format!("\n{line}")).unwrap_or_default(); // under the result, as under the graph
+    if options.blind {
+        return Ok((String::from("hidden -- 'history' shows it"), entry));
+    }
+    match (settings.quiet, settings.verbose) {
+        (true, _) => Ok((result.commands.iter().map(|command| command.subtotal.to_string()).collect::<Vec<String>>().join("\n"), entry)),
+        (false, true) => Ok((format!("{}\n{result}{narrated}", dice::headless::breakdown(&log, &result.commands).join("\n")), entry)),
+        (false, false) => Ok((format!("{}{narrated}", result.line), entry)),
+    }
+}
+
+fn log_csv(settings: &Settings, input: &str, result: &str, total: i16) { // adds a row for the throw (timestamp, command, results, total) to the CSV file, if there is one
+    let Some(path) = settings.csv.as_ref().filter(|_| !settings.sandbox) else { return };
+    let quote = |field: &str| format!("\"{}\"", field.replace('"', "\"\"")); // commas and quotes are common in rolls
+    let timestamp = dice::history::now();
+    let new = fs::metadata(path).map(|file| file.len() == 0).unwrap_or(true);
+    let row = format!("{timestamp},{},{},{total}\n", quote(input.trim()), quote(result));
+    let written = OpenOptions::new().create(true).append(true).open(path).and_then(|mut file| {
+        if new {
+            file.write_all(b"timestamp,command,results,total\n")?;
+        }
+        file.write_all(row.as_bytes())
+    });
+    if written.is_err() {
+        eprintln!("Could not write to {}", path.display());
+    }
+}
+
+fn log_svg(settings: &Settings, input: &str, result: &RollResult) { // draws the throw into the SVG file, if there is one
+    let Some(path) = settings.svg.as_ref().filter(|_| !settings.sandbox) else { return };
+    if let Err(error) = dice::svg::write(path, input.trim(), result) {
+        eprintln!("{error}");
+    }
+}
+
+fn log_history(settings: &Settings, entry: &Entry) { // keeps the throw in the history file
+    let Some(path) = settings.history.as_ref().filter(|_| !settings.sandbox) else { return };
+    if let Some(folder) = path.parent() {
+        let _ = fs::create_dir_all(folder); // a new profile's folder, if need be -- record() reports any failure
+    }
+    if let Err(error) = dice::history::record(path, entry) {
+        eprintln!("{error}");
+    }
+}
+
+fn home_path(profile: Option<&str>, name: &str) -> Option<PathBuf> { // aliases, macros, and settings are kept in the home directory ("~/.dice_macros"), or in a folder per profile ("~/.dice_profiles/campaign/macros")
+    let home = PathBuf::from(env::var_os("HOME")?);
+    match profile {
+        Some(profile) => Some(home.join(".dice_profiles").join(profile).join(name)),
+        None => Some(home.join(format!(".dice_{name}"))),
+    }
+}
+
+fn is_profile_name(name: &str) -> bool { // it becomes a folder name, so keep it to one plain word
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn load_settings(profile: Option<&str>, flags: Settings) -> Settings { // reads 'no_anim = true' style lines from the config file, skipping anything else. flags win over the file
+    let mut settings = flags;
+    settings.history = home_path(profile, "history");
+    let contents = home_path(profile, "config").and_then(|path| fs::read_to_string(path).ok()).unwrap_or_default();
+    for line in contents.lines() {
+        let Some((key, value)) = line.split_once('=') else { continue };
+        let on = value.trim() == "true";
+        match key.trim() {
+            "no_anim" => settings.no_anim |= on,
+            "plain" => settings.plain |= on,
+            "rng" if settings.rng.is_none() => settings.rng = Backend::parse(value),
+            "csv" if settings.csv.is_none() => settings.csv = Some(PathBuf::from(value.trim())),
+            "watch_open" if !value.trim().is_empty() => settings.watch_open = Some(value.trim().to_string()),
+            "watch_close" if !value.trim().is_empty() => settings.watch_close = Some(value.trim().to_string()),
+            key if key.starts_with("narrate ") && !settings.narration.add(&key["narrate ".len()..], value) => { // kept if it reads, or else
+                eprintln!("Could not read the trigger in the config line '{}'", line.trim());
+            },
+            key if key.starts_with("weighted ") => {
+                if let Err(error) = dice::weighted::define(&key["weighted ".len()..], value) {
+                    eprintln!("Could not read the weighted die in the config line '{}': {error}", line.trim());
+                }
+            },
+            _ => (),
+        }
+    }
+    settings
+}
+
+fn load_macros(profile: Option<&str>) -> (HashMap<String, String>, HashMap<String, Vec<String>>) { // reads back 'alias attack = d20+7' and 'macro full = d20+7 | 2d6+3' lines, skipping anything else
+    let mut aliases = HashMap::new();
+    let mut macros = HashMap::new();
+    let contents = home_path(profile, "macros").and_then(|path| fs::read_to_string(path).ok()).unwrap_or_default();
+    for line in contents.lines() {
+        let Some((name, roll)) = line.split_once('=') else { continue };
+        let roll = roll.trim();
+        if let Some(name) = name.trim().strip_prefix("alias ") {
+            aliases.insert(name.trim().to_lowercase(), roll.to_string());
+        } else if let Some(name) = name.trim().strip_prefix("macro ") {
+            macros.insert(name.trim().to_lowercase(), roll.split('|').map(|input| input.trim().to_string()).collect());
         }
     }
+    (aliases, macros)
+}
+
+fn load_named_dice(profile: Option<&str>) -> HashMap<String, NamedDie> { // reads back 'name, kind, seed, rolls, ones, sum, benched' lines, tab-separated, skipping anything that doesn't read
+    let contents = home_path(profile, "named_dice").and_then(|path| fs::read_to_string(path).ok()).unwrap_or_default();
+    contents.lines()
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split('\t').collect();
+            let [name, kind, seed, rolls, ones, sum, benched] = columns[..] else { return None };
+            let die = NamedDie {
+                kind: kind.to_string(),
+                seed: seed.parse().ok()?,
+                rolls: rolls.parse().ok()?,
+                ones: ones.parse().ok()?,
+                sum: sum.parse().ok()?,
+                benched: benched == "benched",
+            };
+            Some((name.to_string(), die))
+        })
+        .collect()
+}
+
+fn save_named_dice(profile: Option<&str>, named: &HashMap<String, NamedDie>) { // writes the whole file again, like the macros
+    let Some(path) = home_path(profile, "named_dice") else { return };
+    if let Some(folder) = path.parent() {
+        let _ = fs::create_dir_all(folder);
+    }
+    let mut lines: Vec<String> = named.iter()
+        .map(|(name, die)| format!("{name}\t{}\t{}\t{}\t{}\t{}\t{}", die.kind, die.seed, die.rolls, die.ones, die.sum, if die.benched { "benched" } else { "playing" }))
+        .collect();
+    lines.sort();
+    if fs::write(path, lines.join("\n") + "\n").is_err() {
+        println!("Could not save to the named dice file");
+    }
+}
+
+fn save_macros(profile: Option<&str>, aliases: &HashMap<String, String>, macros: &HashMap<String, Vec<String>>) { // writes the whole file again, so it always matches what the session has
+    let Some(path) = home_path(profile, "macros") else { return };
+    if let Some(folder) = path.parent() {
+        let _ = fs::create_dir_all(folder); // a new profile's folder, if need be -- the write below reports any failure
+    }
+    let mut lines: Vec<String> = aliases.iter().map(|(name, roll)| format!("alias {name} = {roll}")).collect();
+    lines.extend(macros.iter().map(|(name, inputs)| format!("macro {name} = {}", inputs.join(" | "))));
+    lines.sort();
+    if fs::write(path, lines.join("\n") + "\n").is_err() {
+        println!("Could not save to the macros file");
+    }
+}
+
+fn expand_aliases(input: &str, aliases: &HashMap<String, String>) -> String { // swaps each comma-separated part that's an alias name for its roll, e.g. "attack, 2d6" for "d20+7, 2d6"
+    input.split(',')
+        .map(|part| aliases.get(&part.trim().to_lowercase()).map_or(part.trim(), |roll| roll.as_str()))
+        .collect::<Vec<&str>>()
+        .join(", ")
+}
+
+fn burst_file(input: &str) -> Option<String> { // "burst attacks.txt" becomes a burst of the file's expressions, one per line (blank lines skipped)
+    let path = input.trim().strip_prefix("burst ")?.trim();
+    let contents = fs::read_to_string(path).ok()?;
+    let lines: Vec<&str> = contents.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    Some(format!("burst {}", lines.join(", ")))
+}
+
+fn fill_prompts(input: &str, answers: &mut HashMap<String, String>, events: &mut Events) -> String { // asks for each "?name" in the input (e.g. "d20+5 vs ?target_ac") and puts the answer in its place
+    let mut filled = String::new();
+    let mut rest = input;
+    while let Some(start) = rest.find('?') {
+        filled.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(after.len());
+        let name = &after[..end];
+        if name.is_empty() { // a lone '?' isn't a prompt
+            filled.push('?');
+        } else {
+            let answer = answers.entry(name.to_lowercase()).or_insert_with(|| {
+                print!("{name}? ");
+                stdout().flush().unwrap();
+                events.line().trim().to_string()
+            });
+            filled.push_str(answer);
+        }
+        rest = &after[end..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+fn roll(input: &str, settings: &Settings, seed: Option<u64>, events: &mut Events) -> Roll { // parses and throws one line of input, from the given seed or a new one
+    if settings.plain {
+        return roll_headless(input, settings, seed);
+    }
+    let (mut options, parsed) = match input_handling::get_options(input) {
+        Ok(options) => options,
+        Err(error) => {
+            println!("{error}");
+            return Roll::Invalid;
+        },
+    };
+    if options.blind {
+        return roll_headless(input, settings, seed); // the table would show the dice
+    }
+    let seed = options.seed.or(seed).unwrap_or_else(rand::random); // a '--seed' on the roll itself wins
+    options.no_anim |= settings.no_anim;
+    options.seed = Some(seed);
+    options.rng = settings.rng.unwrap_or_default();
+    match input_handling::generate_dice(parsed, &options) {
+        Ok((dice, log)) => {
+            let Thrown { result, working, exit } = dice::throw(dice, log, options, &settings.narration, events);
+            log_csv(settings, input, &result.line, result.total);
+            log_svg(settings, input, &result);
+            let entry = Entry::new(input, &result.faces(), &result.rerolled(), result.total, seed, true);
+            log_history(settings, &entry);
+            match exit {
+                true => Roll::Exit(entry),
+                false => {
+                    println!("Result: {working}");
+                    Roll::Done(entry)
+                },
+            }
+        },
+        Err(error) => {
+            println!("{error}");
+            Roll::Invalid
+        },
+    }
 }
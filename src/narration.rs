@@ -0,0 +1,108 @@
+use crate::D;
+use crate::headless::CommandResult;
+
+// lines printed under a throw's results to describe how it went, set in the config, e.g.
+//     narrate crit = A devastating blow! {label} deals {total}
+//     narrate fumble = Your weapon slips from your grasp...
+//     narrate 15-19 = A solid hit
+//     narrate 20+ = Legendary!
+// a natural 20 or 1 on a d20 that counts is narrated first, then the first band the total falls in.
+// bands can be negative ("-5--1"). '{total}' is filled in with the throw's total, and '{label}' with the label (or roll)
+// of the command it's about
+
+#[derive(Clone, Default)]
+pub struct Narration {
+    crit: Option<String>,
+    fumble: Option<String>,
+    bands: Vec<(i16, i16, String)>, // lowest and highest totals, and the line, in the order given
+}
+
+impl Narration {
+    pub fn add(&mut self, trigger: &str, line: &str) -> bool { // keeps a line for its trigger ("crit", "fumble", "10-14", or "20+"), returning false if the trigger can't be read
+        let line = line.trim().to_string();
+        match trigger.trim().to_lowercase().as_str() {
+            "crit" => self.crit = Some(line),
+            "fumble" => self.fumble = Some(line),
+            band => {
+                let (low, high) = match band.strip_suffix('+') {
+                    Some(low) => (low.trim().parse::<i16>().ok(), Some(i16::MAX)),
+                    None => match band.char_indices().skip(1).find(|&(at, c)| c == '-' && band[..at].trim_end().ends_with(|c: char| c.is_ascii_digit())) { // the dash after a number, not a minus sign
+                        Some((at, _)) => (band[..at].trim().parse::<i16>().ok(), band[at + 1..].trim().parse::<i16>().ok()),
+                        None => (band.parse::<i16>().ok(), band.parse::<i16>().ok()), // a single total
+                    },
+                };
+                match (low, high) {
+                    (Some(low), Some(high)) if low <= high => self.bands.push((low, high, line)),
+                    _ => return false,
+                }
+            },
+        }
+        true
+    }
+
+    pub fn narrate(&self, commands: &[CommandResult], total: i16) -> Option<String> { // the line for a throw, if any of the triggers were met
+        let natural = |face: u16| commands.iter().find(|command| { // the first command whose counted d20 landed on the face
+            command.throws[command.selected].iter().zip(&command.kept[command.selected])
+                .any(|((kind, landed), kept)| *kind == D::Twenty && *landed == face && *kept)
+        });
+        let (line, command) = match (&self.crit, natural(20), &self.fumble, natural(1)) {
+            (Some(line), Some(command), _, _) => (line, command),
+            (_, _, Some(line), Some(command)) => (line, command),
+            _ => {
+                let (_, _, line) = self.bands.iter().find(|(low, high, _)| (*low..=*high).contains(&total))?;
+                (line, commands.first()?)
+            },
+        };
+        let label = command.label.as_ref().unwrap_or(&command.command);
+        Some(line.replace("{total}", &total.to_string()).replace("{label}", label))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_handling::get_command_values;
+
+    fn landed(input: &str, faces: &[u16]) -> Vec<CommandResult> { // a command's result as if its dice landed on the faces
+        let command = get_command_values(input).unwrap();
+        vec![CommandResult::new(&command, vec![faces.iter().map(|face| (command.kind, *face)).collect()])]
+    }
+
+    fn narration(lines: &[(&str, &str)]) -> Narration {
+        let mut narration = Narration::default();
+        for (trigger, line) in lines {
+            assert!(narration.add(trigger, line), "{trigger}");
+        }
+        narration
+    }
+
+    #[test]
+    fn triggers() {
+        let mut narration = Narration::default();
+        for unreadable in ["often", "5-", "9-3", "x+", "1-2-3"] {
+            assert!(!narration.add(unreadable, "line"), "{unreadable}");
+        }
+        assert!(narration.add(" CRIT ", "line"));
+        assert!(narration.add("-5--1", "line"));
+        assert!(narration.add("-3", "line"));
+        assert!(narration.add("20+", "line"));
+    }
+
+    #[test]
+    fn bands() {
+        let narration = narration(&[("-5--1", "Ouch"), ("0", "Nothing"), ("10-14", "A hit on {label}"), ("15+", "{total}!")]);
+        assert_eq!(narration.narrate(&landed("d6-6", &[3]), -3).as_deref(), Some("Ouch"));
+        assert_eq!(narration.narrate(&landed("d6-6", &[6]), 0).as_deref(), Some("Nothing"));
+        assert_eq!(narration.narrate(&landed("2d6 # sword", &[6, 6]), 12).as_deref(), Some("A hit on sword"));
+        assert_eq!(narration.narrate(&landed("3d6", &[6, 6, 6]), 18).as_deref(), Some("18!"));
+        assert_eq!(narration.narrate(&landed("3d6", &[1, 2, 3]), 6), None);
+    }
+
+    #[test]
+    fn naturals() {
+        let narration = narration(&[("crit", "Crit for {total} on {label}"), ("fumble", "Fumble"), ("1+", "Fine")]);
+        assert_eq!(narration.narrate(&landed("d20+2", &[20]), 22).as_deref(), Some("Crit for 22 on 1d20+2"));
+        assert_eq!(narration.narrate(&landed("d20+2", &[1]), 3).as_deref(), Some("Fumble"));
+        assert_eq!(narration.narrate(&landed("2d20kl1", &[20, 5]), 5).as_deref(), Some("Fine")); // the 20 was dropped
+    }
+}
@@ -0,0 +1,78 @@
+use rand::{Error, Rng, RngCore, SeedableRng};
+use rand::rngs::{OsRng, SmallRng, StdRng};
+
+// where the dice get their randomness. every die and headless throw draws from an RngSource,
+// so the generator can be swapped without touching the rolling code
+
+#[derive(Clone, Copy, Default, PartialEq)]
+pub enum Backend { // which generator to use, from the config or '--rng'
+    #[default]
+    Standard, // ChaCha, seeded per throw so it can be replayed
+    Os, // the operating system's secure generator. can't be seeded, so throws can't be replayed
+    Fast, // xoshiro, seeded per throw -- quicker but not secure
+}
+
+impl Backend {
+    pub fn parse(name: &str) -> Option<Backend> { // the name given in the config or flag, e.g. "fast"
+        match name.trim().to_lowercase().as_str() {
+            "standard" | "std" => Some(Backend::Standard),
+            "os" | "secure" => Some(Backend::Os),
+            "fast" | "xoshiro" => Some(Backend::Fast),
+            _ => None,
+        }
+    }
+}
+
+pub enum RngSource {
+    Standard(Box<StdRng>), // boxed, as it keeps a block of output buffered
+    Os(OsRng),
+    Fast(SmallRng),
+}
+
+impl RngSource {
+    pub fn new(backend: Backend, seed: u64) -> RngSource { // the seed is ignored by the OS generator
+        match backend {
+            Backend::Standard => RngSource::Standard(Box::new(StdRng::seed_from_u64(seed))),
+            Backend::Os => RngSource::Os(OsRng),
+            Backend::Fast => RngSource::Fast(SmallRng::seed_from_u64(seed)),
+        }
+    }
+}
+
+pub fn mix(seed: u64) -> u64 { // spreads out nearby seeds (a throw's seed plus each die's id) so the dice don't start off alike
+    StdRng::seed_from_u64(seed).gen()
+}
+
+impl RngCore for RngSource {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            RngSource::Standard(rng) => rng.next_u32(),
+            RngSource::Os(rng) => rng.next_u32(),
+            RngSource::Fast(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            RngSource::Standard(rng) => rng.next_u64(),
+            RngSource::Os(rng) => rng.next_u64(),
+            RngSource::Fast(rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            RngSource::Standard(rng) => rng.fill_bytes(dest),
+            RngSource::Os(rng) => rng.fill_bytes(dest),
+            RngSource::Fast(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        match self {
+            RngSource::Standard(rng) => rng.try_fill_bytes(dest),
+            RngSource::Os(rng) => rng.try_fill_bytes(dest),
+            RngSource::Fast(rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::error::DiceError;
+use crate::headless::{self, RollResult};
+use crate::input_handling::{generate_dice, get_options, Command, ThrowOptions};
+
+use std::io::{stdin, stdout, Write};
+
+use serde_json::{json, Value};
+
+// a small JSON-RPC 2.0 server over stdin/stdout, one message per line, for editor plugins.
+// 'validate' checks an expression as it's typed, 'roll' throws it headlessly:
+//     {"jsonrpc": "2.0", "id": 1, "method": "roll", "params": {"expression": "2d6+3"}}
+// a roll's result lists any faces its dice were rerolled from, each with what replaced it and why, for audits:
+//     {"result": "2d6r1: 9 => 9", "total": 9, "rerolls": [{"command": 0, "throw": 0, "die": 1, "from": 1, "to": 4, "reason": "rule"}]}
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const ROLL_ERROR: i64 = -32000; // the expression didn't parse, or needs the table
+
+pub fn serve() { // answers each request line until input closes
+    for line in stdin().lines().map_while(Result::ok) {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = respond(&line) {
+            let mut out = stdout();
+            writeln!(out, "{response}").unwrap();
+            out.flush().unwrap();
+        }
+    }
+}
+
+pub fn respond(line: &str) -> Option<Value> { // the response to one request, or None for a notification (no id)
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(_) => return Some(error(Value::Null, PARSE_ERROR, "Parse error")),
+    };
+    let id = request.get("id").cloned();
+    let expression = request.pointer("/params/expression").and_then(Value::as_str);
+    let outcome = match (request.get("method").and_then(Value::as_str), expression) {
+        (Some("validate"), Some(expression)) => Ok(match parse(expression) {
+            Ok(_) => json!({ "valid": true }),
+            Err(error) => json!({ "valid": false, "message": error.to_string() }), // a diagnostic, not a failed request
+        }),
+        (Some("roll"), Some(expression)) => parse(expression)
+            .and_then(|(log, options)| Ok(headless::finish(&options, headless::roll(&log, &options)?)))
+            .map(|result| json!({ "result": result.line, "total": result.total, "rerolls": rerolls(&result) }))
+            .map_err(|error| (ROLL_ERROR, error.to_string())),
+        (Some("validate" | "roll"), None) => Err((INVALID_PARAMS, String::from("Params need an 'expression' string"))),
+        _ => Err((METHOD_NOT_FOUND, String::from("Method not found"))),
+    };
+    let id = id?;
+    Some(match outcome {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => error(id, code, &message),
+    })
+}
+
+fn parse(expression: &str) -> Result<(Vec<Command>, ThrowOptions), DiceError> { // the commands and options an expression comes to, as the prompt would read it
+    let (options, input) = get_options(expression)?;
+    let (_, log) = generate_dice(input, &options)?;
+    Ok((log, options))
+}
+
+fn rerolls(result: &RollResult) -> Vec<Value> {
+    result.commands.iter().enumerate()
+        .flat_map(|(command, rolled)| rolled.rerolled.iter().map(move |rerolled| json!({
+            "command": command,
+            "throw": rerolled.throw,
+            "die": rerolled.die,
+            "from": rerolled.from,
+            "to": rerolled.to,
+            "reason": rerolled.reason.to_string(),
+        })))
+        .collect()
+}
+
+fn error(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, params: Value) -> Value {
+        let line = json!({ "jsonrpc": "2.0", "id": 7, "method": method, "params": params }).to_string();
+        respond(&line).expect("a request with an id gets a response")
+    }
+
+    #[test]
+    fn validates() {
+        assert_eq!(request("validate", json!({ "expression": "2d6+3" }))["result"], json!({ "valid": true }));
+        let invalid = request("validate", json!({ "expression": "2d6++" }));
+        assert_eq!(invalid["result"]["valid"], json!(false));
+        assert_eq!(invalid["result"]["message"], json!("Modifier error"));
+    }
+
+    #[test]
+    fn rolls() {
+        let rolled = request("roll", json!({ "expression": "3d1+2" })); // one face, so always the same
+        assert_eq!(rolled["id"], json!(7));
+        assert_eq!(rolled["result"], json!({ "result": "3d1+2: 5 => 5", "total": 5, "rerolls": [] }));
+        let rerolled = request("roll", json!({ "expression": "8d2r1 --seed 4" })); // every 1 comes back a 2 in the end
+        assert_eq!(rerolled["result"]["total"], json!(16));
+        let rerolls = rerolled["result"]["rerolls"].as_array().unwrap();
+        assert!(!rerolls.is_empty());
+        assert!(rerolls.iter().all(|reroll| reroll["from"] == json!(1) && reroll["reason"] == json!("rule")));
+        let table_only = request("roll", json!({ "expression": "lock 2 4d6" }));
+        assert_eq!(table_only["error"]["code"], json!(ROLL_ERROR));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(respond("{not json").unwrap()["error"]["code"], json!(PARSE_ERROR));
+        assert_eq!(request("fly", json!({}))["error"]["code"], json!(METHOD_NOT_FOUND));
+        assert_eq!(request("roll", json!({ "dice": "d6" }))["error"]["code"], json!(INVALID_PARAMS));
+        assert!(respond(r#"{"jsonrpc": "2.0", "method": "roll", "params": {"expression": "d6"}}"#).is_none()); // a notification
+    }
+}
@@ -0,0 +1,122 @@
+use crate::D;
+use crate::headless::RollResult;
+
+use std::fs;
+use std::path::Path;
+
+// draws a throw's results as an SVG picture, laid out like the graph in graph.rs,
+// for pasting roll summaries into campaign wikis and session recaps
+
+const CELL_WIDTH: f32 = 8.4; // a monospace character at the font size below
+const ROW_HEIGHT: f32 = 18.0;
+const FONT_SIZE: u16 = 14;
+const DIVIDER: &str = "------------------------------------";
+
+const TEXT: &str = "#d0d0d0";
+const GREY: &str = "#707070"; // the greyed-out faces: dropped dice and throws not taken
+const GREEN: &str = "#5fd75f";
+const RED: &str = "#ff5f5f";
+const BACKGROUND: &str = "#1c1c1c";
+
+struct Cell { // a run of text at a column and row of the grid
+    col: u16,
+    row: u16,
+    text: String,
+    colour: &'static str,
+}
+
+pub fn render(title: &str, result: &RollResult) -> String { // the picture of a throw, headed with the title (usually the roll as typed)
+    // the same columns as the graph, measured from its left edge instead of the terminal's centre
+    let command_col = 1;
+    let arrow_col = 10;
+    let result_col = 13;
+    let big_arrow_col = 18;
+    let running_col = 21;
+    let modifier_col = 26;
+    let equals_col = 31;
+    let sum_col = 33;
+    let width = DIVIDER.len() as u16 + 2;
+
+    let mut cells = vec![
+        Cell { col: command_col, row: 2, text: String::from("Rolls    Results         Mod  Total"), colour: TEXT },
+        Cell { col: command_col, row: 3, text: String::from(DIVIDER), colour: TEXT },
+    ];
+    let mut command_row = 4; // the row the next command goes on
+    let mut running_row = 3; // and the row its totals go on
+
+    for command in &result.commands {
+        cells.push(Cell { col: command_col, row: command_row, text: command.command.clone(), colour: TEXT });
+
+        let mut line = 0;
+        for (throw, (faces, kept)) in command.throws.iter().zip(&command.kept).enumerate() {
+            for ((kind, face), kept) in faces.iter().zip(kept) {
+                let colour = match face {
+                    _ if throw != command.selected || !kept => GREY,
+                    20 if *kind == D::Twenty => GREEN,
+                    1 if *kind == D::Twenty => RED,
+                    _ => TEXT,
+                };
+                cells.push(Cell { col: arrow_col, row: command_row + line, text: String::from("->"), colour: TEXT });
+                cells.push(Cell { col: result_col, row: command_row + line, text: kind.label(*face), colour });
+                line += 1;
+            }
+        }
+        if let Some(label) = &command.label { // under the results, cut to fit before the arrows like on the graph
+            const LABEL_WIDTH: usize = 8;
+            cells.push(Cell { col: command_col, row: command_row + line, text: label.chars().take(LABEL_WIDTH).collect(), colour: TEXT });
+            line += 1;
+        }
+
+        command_row += line + 1;
+        running_row += line;
+        let total = command.totals[command.selected];
+        let sign = if command.modifier >= 0 { '+' } else { '-' };
+        cells.push(Cell { col: big_arrow_col, row: running_row, text: String::from("=>"), colour: TEXT });
+        cells.push(Cell { col: running_col, row: running_row, text: total.to_string(), colour: TEXT });
+        cells.push(Cell { col: modifier_col, row: running_row, text: format!("{sign} {}", command.modifier.abs()), colour: TEXT });
+        cells.push(Cell { col: equals_col, row: running_row, text: String::from("="), colour: TEXT });
+        cells.push(Cell { col: sum_col, row: running_row, text: command.subtotal.to_string(), colour: TEXT });
+        cells.push(Cell { col: command_col, row: running_row + 1, text: String::from(DIVIDER), colour: TEXT });
+        running_row += 1;
+    }
+    cells.push(Cell { col: sum_col - 2, row: running_row + 1, text: format!("= {}", result.total), colour: TEXT });
+
+    // the picture
+    let (pixel_width, pixel_height) = (width as f32 * CELL_WIDTH, (running_row + 4) as f32 * ROW_HEIGHT);
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{pixel_width:.0}\" height=\"{pixel_height:.0}\" font-family=\"monospace\" font-size=\"{FONT_SIZE}\" xml:space=\"preserve\">\n");
+    svg.push_str(&format!("  <rect width=\"100%\" height=\"100%\" fill=\"{BACKGROUND}\"/>\n"));
+    svg.push_str(&format!("  <text x=\"{:.1}\" y=\"{:.1}\" fill=\"{TEXT}\" text-anchor=\"middle\">{}</text>\n", pixel_width / 2.0, baseline(0), escape(title)));
+    for cell in cells {
+        svg.push_str(&format!("  <text x=\"{:.1}\" y=\"{:.1}\" fill=\"{}\">{}</text>\n", cell.col as f32 * CELL_WIDTH, baseline(cell.row), cell.colour, escape(&cell.text)));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+pub fn write(path: &Path, title: &str, result: &RollResult) -> Result<(), &'static str> { // renders the throw into the file, replacing what was there
+    fs::write(path, render(title, result)).map_err(|_| "Could not write the SVG file")
+}
+
+fn baseline(row: u16) -> f32 { // where text on a row of the grid sits, leaving a row above as a margin (and one below, in the height)
+    (row + 1) as f32 * ROW_HEIGHT + FONT_SIZE as f32 / 2.0
+}
+
+fn escape(text: &str) -> String { // labels and titles can hold characters that mean something in XML
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_the_throw() {
+        let result = crate::roll("2d1+3 # <fire> & ice, d20-1").unwrap();
+        let svg = render("2d1+3 <fire>", &result);
+        assert!(svg.starts_with("<svg ") && svg.ends_with("</svg>\n"));
+        assert!(svg.contains(">2d1+3 &lt;fire&gt;</text>")); // the title, escaped
+        assert!(svg.contains(">&lt;fire&gt; &amp;</text>")); // the label, cut to fit and escaped
+        assert!(svg.contains(&format!(">= {}</text>", result.total)));
+        assert_eq!(svg.matches(">-&gt;</text>").count(), 3); // an arrow per die
+    }
+}
@@ -1,15 +1,20 @@
-use crate::Code;
 use crate::D;
-use crate::input_handling::get_command_values;
+use crate::error::DiceError;
+use crate::events::{Event, Events};
+use crate::headless::{self, Check, CommandResult, Pool, RerollReason, Rerolled, RollResult};
+use crate::narration::Narration;
+use crate::rng::{self, RngSource};
+use crate::input_handling::{Clamp, Code, Command, Factor, Keep, Modifier, Reroll, ThrowOptions};
 use crate::graph::Graph;
 use crate::util::*;
 
 use std::io::{Stdout, Write};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::time::Duration;
 use std::thread;
 
-use termion::{cursor::{self, Goto}, terminal_size, color};
+use termion::{cursor::{self, Goto}, terminal_size, color, style};
 use termion::raw::{RawTerminal, IntoRawMode};
 use termion::screen::{AlternateScreen, IntoAlternateScreen};
 
@@ -17,29 +22,84 @@ use termion::screen::{AlternateScreen, IntoAlternateScreen};
 // largely concerned with displaying objects and information to the screen, and tracking information for each die
 
 const DISPLAY_RESULTS: usize = 5; // for return strings on Normal rolls
+const EXPLOSION_LIMIT: usize = 99; // most dice allowed on the table once explosions are counted
 
 pub struct Table {
     pub surface: RawTerminal<AlternateScreen<Stdout>>, // DOES NOT WORK IN TERMION 3.0.0
-    code: Code,
-    command_log: Vec<String>,
+    command_log: Vec<Command>,
     kinds: HashMap<usize, D>, // ID, kind (updated at start of roll)
     tracker: HashMap<usize, (u16, u16)>, // ID, position (updated repeatedly during rolling)
     results: HashMap<usize, u16>, // ID, face (updated repeatedly during rolling)
-    modifiers: Vec<i16>, // updated at throw
+    groups: HashMap<usize, usize>, // ID, index of the command the die was thrown for
+    exploded: HashMap<usize, usize>, // ID, ID of the die it set off by exploding
+    discarded: HashMap<usize, Vec<(u16, RerollReason)>>, // ID, faces it showed before being rerolled, and why each was
+    seeds: HashMap<usize, u64>, // ID, seed it was last thrown with
+    options: ThrowOptions,
+    locked: HashSet<usize>, // IDs held back from lock mode rerolls
+    saved: HashSet<u16>, // split damage: targets (from 1) that made their save
+    narration: Narration, // lines from the config to print under the graph
     pub graph_on: bool, // whether the results graph is on screen
     pub error_on: bool, // whether the results display error is on screen
 }
 
+impl fmt::Display for Pool { // what the 1s do to the successes, e.g. "- 2 ones" or "glitch!", coloured for the table
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.botch, self.glitched()) {
+            _ if self.botched() => write!(f, "{}botch!{}", color::Fg(color::Magenta), color::Fg(color::Reset)),
+            (true, _) => write!(f, "- {} ones", self.ones),
+            (false, true) if self.successes == 0 => write!(f, "{}{}crit glitch!{}", style::Bold, color::Fg(color::Red), style::Reset),
+            (false, true) => write!(f, "{}glitch!{}", color::Fg(color::Yellow), color::Fg(color::Reset)),
+            (false, false) => write!(f, "no glitch"),
+        }
+    }
+}
+
+struct Attack { // one attacker of a horde
+    natural: u16, // the d20 as it landed
+    total: i16,
+    hit: bool,
+    crit: bool,
+    damage: i16, // nothing on a miss
+}
+
+impl fmt::Display for Attack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (colour, verdict) = match (self.hit, self.crit) {
+            (true, true) => (color::Fg(color::Green).to_string(), "crit"),
+            (true, false) => (color::Fg(color::Reset).to_string(), "hit"),
+            (false, _) => (color::Fg(color::LightBlack).to_string(), "miss"),
+        };
+        write!(f, "{colour}{:>6}{:>7}  {verdict:<6}{:>5}{}", self.natural, self.total, self.damage, color::Fg(color::Reset))
+    }
+}
+
 impl Table {
-    pub fn new(code: Code, modifiers: Vec<i16>, command_log: Vec<String>) -> Table {
+    pub fn new(command_log: Vec<Command>, options: ThrowOptions, narration: Narration) -> Table {
+
+        // dice are thrown in command order, so work out which command each ID belongs to
+        let mut groups = HashMap::new();
+        let mut id = 0;
+        for (group, command) in command_log.iter().enumerate() {
+            for _ in command.dice() {
+                groups.insert(id, group);
+                id += 1;
+            }
+        }
+
         Table { 
             surface: std::io::stdout().into_alternate_screen().unwrap().into_raw_mode().unwrap(),
-            code,
             command_log,
             kinds: HashMap::new(),
             tracker: HashMap::new(),
             results: HashMap::new(),
-            modifiers,
+            groups,
+            exploded: HashMap::new(),
+            discarded: HashMap::new(),
+            seeds: HashMap::new(),
+            options,
+            locked: HashSet::new(),
+            saved: HashSet::new(),
+            narration,
             graph_on: false,
             error_on: false,
         }
@@ -56,34 +116,30 @@ impl Table {
         }
 
         // log new face up, make "eraser" based on old one's length
-        let mut eraser = String::from(" ");
         let kind = self.kinds.get(&id).unwrap();
-        if let Some(old_face) = self.results.insert(id, face) { // RESULTS MAP IS UPDATED HERE
-            if is_two_digits(old_face, *kind) { // erase two spaces if the old face was double-digit (or percentile rolling zero)
-                eraser.push(' ');
-            }
-        }
-
-        // if die shows double digits and you're on the last col, offset draw position one column back (don't modify "actual" position) to prevent overflow
-        let offset: u16;
         let (last_col, _) = terminal_size().unwrap();
-        match new_col == last_col && is_two_digits(face, *kind) {
-            true => offset = 1,
-            false => offset = 0,
-        }
-        
+        let (eraser, old_offset) = match self.results.insert(id, face) { // RESULTS MAP IS UPDATED HERE
+            Some(old_face) => ( // erase as many spaces as the old face took up (e.g. double digits, or a coin), from where it was drawn
+                " ".repeat(kind.width(old_face) as usize),
+                (old_col + kind.width(old_face) - 1).saturating_sub(last_col),
+            ),
+            None => (String::from(" "), 0),
+        };
+
+        // if the face would run past the last col, offset draw position back (don't modify "actual" position) to prevent overflow
+        let offset = (new_col + kind.width(face) - 1).saturating_sub(last_col);
         // ^^ there are fringe - but significant - cases where dice slip through the Die::detect_walls() overflow catcher, that this block prevents
         // basically, you need that block because if a die is single-digit on the second-last column, it could move to the last column
         // and then change to double digits and cause an overflow
 
         // erase old position and redraw at new
-        write!(self.surface, "{}{eraser}{}{face}",
-            Goto(old_col, old_row),
-            Goto(new_col - offset, new_row)
+        let colour = self.group_colour(*self.groups.get(&id).unwrap());
+        write!(self.surface, "{}{eraser}{}{colour}{}{}",
+            Goto(old_col - old_offset, old_row),
+            Goto(new_col - offset, new_row),
+            kind.table_label(face), // e.g. PercentTens rolling 0 shows as "00"
+            color::Fg(color::Reset),
         ).unwrap();
-        if face == 0 && id == 0 { // for case of PercentTens die rolling 0, adds a second zero. Tens spot always has ID 0.
-            write!(self.surface, "0").unwrap();
-        }
         self.surface.flush().unwrap();
     }
 
@@ -96,18 +152,12 @@ impl Table {
             // right edge overflow safety
             let (col, row) = self.tracker.get(id).expect("die location should exist");
             let kind = self.kinds.get(id).unwrap();
-            let offset: u16;
             let (last_col, _) = terminal_size().unwrap();
-            match *col == last_col && is_two_digits(*result, *kind) {
-                true => offset = 1,
-                false => offset = 0,
-            }
+            let offset = (*col + kind.width(*result) - 1).saturating_sub(last_col);
 
             // actually reprint
-            write!(self.surface, "{}{result}", Goto(*col - offset, *row)).unwrap();
-            if *result == 0 && *id == 0 {
-                write!(self.surface, "0").unwrap();
-            }
+            let colour = self.group_colour(*self.groups.get(id).unwrap());
+            write!(self.surface, "{}{colour}{}{}", Goto(*col - offset, *row), kind.table_label(*result), color::Fg(color::Reset)).unwrap();
         }
         
         self.surface.flush().unwrap();
@@ -116,57 +166,303 @@ impl Table {
         self.error_on = false;
     }
 
+    fn group_colour(&self, group: usize) -> String { // in burst mode, each command and its dice share a colour so they can be told apart
+        if !self.options.burst {
+            return String::new();
+        }
+        match group % 6 {
+            0 => color::Fg(color::Cyan).to_string(),
+            1 => color::Fg(color::Yellow).to_string(),
+            2 => color::Fg(color::Magenta).to_string(),
+            3 => color::Fg(color::Blue).to_string(),
+            4 => color::Fg(color::LightGreen).to_string(),
+            _ => color::Fg(color::LightRed).to_string(),
+        }
+    }
+
     pub fn log_kind(&mut self, id: usize, kind: D) {
         self.kinds.insert(id, kind);
     }
 
-    fn full_sum(&self) -> Option<i16> { // adds together all die results and modifiers
-        Some(self.results.values().sum::<u16>() as i16 + self.modifiers.iter().sum::<i16>())
+    pub fn explodes(&self, id: usize, face: u16) -> bool { // whether a settled die should set off another of its kind
+        let kind = self.kinds.get(&id).unwrap();
+        let group = self.groups.get(&id).unwrap();
+        self.command_log[*group].explode && face == kind.as_number() && self.results.len() < EXPLOSION_LIMIT
     }
 
-    fn advantage(&self) -> Option<u16> { // assesses rolls with advantage
-        if self.results.iter().count() > 2 {
-            return None // Cannot roll with advantage on more than two dice
+    pub fn log_explosion(&mut self, parent: usize, id: usize) -> D { // logs a die set off by another, returning its kind
+        let kind = *self.kinds.get(&parent).unwrap();
+        let group = *self.groups.get(&parent).unwrap();
+        self.kinds.insert(id, kind);
+        self.groups.insert(id, group);
+        self.exploded.insert(parent, id);
+        kind
+    }
+
+    pub fn rerolls(&self, id: usize, face: u16) -> bool { // whether a settled die should be thrown again
+        let group = self.groups.get(&id).unwrap();
+        match self.command_log[*group].reroll {
+            Reroll::Never => false,
+            Reroll::Always(value) => face == value,
+            Reroll::Once(value) => face == value && self.crossed_out(id).is_empty(),
+        }
+    }
+
+    pub fn log_reroll(&mut self, id: usize) -> D { // logs the face a die is being rerolled from, returning its kind
+        let face = *self.results.get(&id).unwrap();
+        self.discarded.entry(id).or_default().push((face, RerollReason::Rule));
+        *self.kinds.get(&id).unwrap()
+    }
+
+    pub fn die_rng(&mut self, id: usize) -> RngSource { // the generator to throw a die with, seeded from the one it was last thrown with (or its parent's, if it was set off by exploding), or else the throw's. so a replay matches whatever order the dice settle in
+        let from = match (self.seeds.get(&id), self.exploded.iter().find(|(_, child)| **child == id)) {
+            (Some(last), _) => *last,
+            (None, Some((parent, _))) => !self.seeds[parent],
+            (None, None) => self.options.seed.unwrap_or_default().wrapping_add(id as u64),
+        };
+        let seed = rng::mix(from);
+        self.seeds.insert(id, seed);
+        RngSource::new(self.options.rng, seed)
+    }
+
+    pub fn next_id(&self) -> usize { // IDs run from zero with no gaps
+        self.kinds.len()
+    }
+
+    pub fn can_lock(&self) -> bool {
+        self.options.rerolls.is_some()
+    }
+
+    pub fn rerolls_left(&self) -> u16 {
+        self.options.rerolls.unwrap_or(0)
+    }
+
+    pub fn toggle_lock(&mut self, id: usize) { // locks an unlocked die and vice versa
+        if !self.kinds.contains_key(&id) {
+            return;
+        }
+        if !self.locked.remove(&id) {
+            self.locked.insert(id);
+        }
+    }
+
+    pub fn can_split(&self) -> bool {
+        self.options.split.is_some()
+    }
+
+    pub fn toggle_save(&mut self, target: u16) { // marks a target as saved for half damage and vice versa
+        if target == 0 || target > self.options.split.unwrap_or(0) {
+            return;
+        }
+        if !self.saved.remove(&target) {
+            self.saved.insert(target);
         }
-        Some(*self.results.values().max()?)
     }
 
-    fn disadvantage(&self) -> Option<u16> { // assessing rolls with disadvantage
-        if self.results.iter().count() > 2 {
-            return None // Cannot roll with disadvantage on more than two dice.
+    pub fn take_reroll(&mut self) -> Vec<(usize, D)> { // uses up a lock mode reroll, returning the dice to throw again
+        self.options.rerolls = self.options.rerolls.map(|n| n.saturating_sub(1));
+        let mut dice: Vec<(usize, D)> = self.kinds.iter()
+            .filter(|(id, _)| !self.locked.contains(id))
+            .map(|(id, kind)| (*id, *kind))
+            .collect();
+        dice.sort_by_key(|k| k.0);
+        for (id, _) in &dice { // kept for the result, but not crossed out on the graph -- it would soon run out of room
+            let face = self.results[id];
+            self.discarded.entry(*id).or_default().push((face, RerollReason::Lock));
         }
-        Some(*self.results.values().min()?)
+        dice
     }
 
-    fn percent_sum(&self) -> Option<u16> { // similar to regular sum but has a caveat if they're both zero
-        if self.results.iter().count() > 2 {
-            return None // Cannot roll percent on more than two dice.
+    fn crossed_out(&self, id: usize) -> Vec<String> { // the faces a die was rerolled from, formatted to be crossed out
+        self.discarded.get(&id).into_iter().flatten()
+            .filter(|(_, reason)| *reason == RerollReason::Rule)
+            .map(|(face, _)| format!("{}{}{face}{}", style::CrossedOut, color::Fg(color::LightBlack), style::Reset))
+            .collect()
+    }
+
+    fn group_results(&self, group: usize) -> Vec<(usize, u16)> { // ID and face for each die of a command, with every explosion chain kept together in order
+        self.group_chains(group).concat()
+    }
+
+    fn group_chains(&self, group: usize) -> Vec<Vec<(usize, u16)>> { // ID and face for each die of a command, one explosion chain per die thrown
+        let mut roots: Vec<usize> = self.groups.iter()
+            .filter(|(id, g)| **g == group && !self.exploded.values().any(|child| child == *id))
+            .map(|(id, _)| *id)
+            .collect();
+        roots.sort();
+        let mut chains = Vec::new();
+        for root in roots {
+            let mut chain = Vec::new();
+            let mut next = Some(root);
+            while let Some(id) = next {
+                chain.push((id, *self.results.get(&id).expect("results should exist")));
+                next = self.exploded.get(&id).copied();
+            }
+            chains.push(chain);
         }
-        let mut sum = self.results.values().sum::<u16>(); 
+        chains
+    }
+
+    fn full_sum(&self) -> Option<i16> { // adds together all kept die results and modifiers
+        let mut sum = 0;
+        for (group, command) in self.command_log.iter().enumerate() {
+            sum += self.throw_total(group, self.selected_throw(group))? + command.modifier();
+        }
+        Some(sum)
+    }
+
+    fn fate_ladder(&self) -> Option<&'static str> { // the Fate ladder adjective for the sum, if every command rolled Fudge dice
+        if self.command_log.iter().any(|command| command.kind != D::Fudge || command.target.is_some()) {
+            return None;
+        }
+        Some(headless::fate_ladder(self.full_sum()?))
+    }
+
+    fn check(&self) -> Option<Check> { // the sum against the skill it had to roll at or under, if the throw was a check
+        let skill = self.options.versus? as i16;
+        Some(Check::new(skill, self.throw_total(0, 0)?, self.full_sum()?)) // load_dice() makes sure the check is a single 3d6
+    }
+
+    fn split_damage(&self) -> Vec<(u16, i16, bool)> { // target number, damage taken, and whether they saved for half
+        let sum = match self.full_sum() {
+            Some(sum) => sum,
+            None => return Vec::new(),
+        };
+        (1..=self.options.split.unwrap_or(0))
+            .map(|target| match self.saved.contains(&target) {
+                true => (target, sum / 2, true), // rounds down
+                false => (target, sum, false),
+            })
+            .collect()
+    }
+
+    fn horde_attacks(&self) -> Vec<Attack> { // each attacker's roll against the AC and the damage it does. a natural 20 always hits and doubles the damage dice, a 1 always misses
+        let ac = match self.options.horde {
+            Some(ac) => ac as i16,
+            None => return Vec::new(),
+        };
+        (0..self.command_log.len() / 2)
+            .map(|attacker| {
+                let (attack, damage) = (attacker * 2, attacker * 2 + 1); // commands alternate attack, damage
+                let throw = self.selected_throw(attack);
+                let natural = self.throw_results(attack, throw)[0].1;
+                let total = self.throw_total(attack, throw).expect("Should have been able to total the attack") + self.command_log[attack].modifier();
+                let crit = natural == 20;
+                let hit = crit || (natural != 1 && total >= ac);
+                let dice = self.throw_total(damage, self.selected_throw(damage)).expect("Should have been able to total the damage");
+                let damage = match (hit, crit) {
+                    (false, _) => 0,
+                    (true, false) => dice + self.command_log[damage].modifier(),
+                    (true, true) => dice * 2 + self.command_log[damage].modifier(),
+                };
+                Attack { natural, total, hit, crit, damage }
+            })
+            .collect()
+    }
+
+    fn versus_label(&self) -> String { // e.g. " vs 14", for the header
+        self.options.versus.map(|skill| format!(" vs {skill}")).unwrap_or_default()
+    }
+
+    fn throw_results(&self, group: usize, throw: usize) -> Vec<(usize, u16)> { // ID and face for each die of one throw of a command. advantage rolls are thrown more than once, everything else once
+        if self.command_log[group].code == Code::Wild { // the trait die and the wild die ace separately, so their chains can differ in length
+            return self.group_chains(group).swap_remove(throw);
+        }
+        let results = self.group_results(group);
+        let per_throw = results.len() / self.command_log[group].code.throws();
+        results[throw * per_throw..(throw + 1) * per_throw].to_vec()
+    }
+
+    fn throw_total(&self, group: usize, throw: usize) -> Option<i16> { // what one throw of a command comes to, before the modifier
+        let command = &self.command_log[group];
+        if matches!(command.kind, D::PercentTens | D::PercentHundreds) {
+            return Some(self.percent_sum(group, throw)? as i16);
+        }
+        let scores: Vec<i16> = self.throw_results(group, throw).iter().map(|(_, face)| command.kind.score(*face)).collect();
+        Some(command.value(&scores))
+    }
+
+    fn advantage(&self, group: usize) -> Option<usize> { // assesses rolls with advantage, returning the best throw (the first, on a tie)
+        let mut best = 0;
+        for throw in 1..self.command_log[group].code.throws() {
+            if self.throw_total(group, throw)? > self.throw_total(group, best)? {
+                best = throw;
+            }
+        }
+        Some(best)
+    }
+
+    fn disadvantage(&self, group: usize) -> Option<usize> { // assessing rolls with disadvantage, returning the worst throw
+        let mut worst = 0;
+        for throw in 1..self.command_log[group].code.throws() {
+            if self.throw_total(group, throw)? < self.throw_total(group, worst)? {
+                worst = throw;
+            }
+        }
+        Some(worst)
+    }
+
+    fn selected_throw(&self, group: usize) -> usize { // which throw of a command counts
+        match self.command_log[group].code {
+            Code::Advantage(_) | Code::Wild => self.advantage(group).expect("Should have been able to assess advantage"),
+            Code::Disadvantage(_) => self.disadvantage(group).expect("Should have been able to assess disadvantage"),
+            _ => 0,
+        }
+    }
+
+    fn percent_sum(&self, group: usize, throw: usize) -> Option<u16> { // similar to regular sum but has a caveat if they're all zero
+        let results = self.throw_results(group, throw);
+        if results.len() != 2 && results.len() != 3 {
+            return None // a percentile throw is exactly two dice (three for d1000)
+        }
+        let mut sum = results.iter().map(|(_, face)| face).sum::<u16>();
         if sum == 0 {
-            sum = 100; // if you roll two zeros, that's actually 100
+            sum = 10u16.pow(results.len() as u32); // if you roll all zeros, that's actually 100 (or 1000)
         }
         Some(sum)
     }
 
-    pub fn print_throw(&mut self) {
+    fn pool_result(&self, group: usize) -> Option<Pool> { // successes and 1s for a botch or glitch pool
+        let command = &self.command_log[group];
+        let scores: Vec<i16> = self.group_results(group).iter().map(|(_, face)| command.kind.score(*face)).collect();
+        Pool::new(command, &scores)
+    }
+
+    fn shared_code(&self) -> Option<Code> { // the code every command was rolled with, if they agree
+        let code = self.command_log.first()?.code;
+        self.command_log.iter().all(|command| command.code == code).then_some(code)
+    }
+
+    fn steps(factor: Factor, clamp: Clamp) -> String { // what's done to a command's dice total before the modifier, written out for the one-liner
+        let mut steps = String::new();
+        if factor != Factor::One {
+            steps.push_str(&format!(" {factor}"));
+        }
+        if clamp.is_set() {
+            steps.push_str(&format!(" {clamp}"));
+        }
+        steps
+    }
+
+    pub fn print_throw(&mut self, events: &mut Events) {
 
         // display pending throws at centre
         let (mut col, mut row) = terminal_centre();
         row -= self.command_log.len() as u16 / 2;
         let roll_msg = "Rolling:";
         write!(self.surface, "{}{roll_msg}", Goto(centre(roll_msg), row - 2)).unwrap();
-        for item in self.command_log.iter() {
-            write!(self.surface, "{}{item}", Goto(centre(item), row)).unwrap();
+        for command in self.command_log.iter() {
+            let mut item = match command.code {
+                Code::Normal => command.to_string(),
+                code => format!("{command} ({code})"), // special rolls are labelled
+            };
+            if let Some(label) = &command.label {
+                item = format!("{label}: {item}");
+            }
+            write!(self.surface, "{}{item}", Goto(centre(&item), row)).unwrap();
             row += 1;
         }
-        let (adv, disadv, percent) = ("Advantage", "Disadvantage", "Percentile");
-        match self.code {
-            Code::Advantage => write!(self.surface, "{}{adv}", Goto(centre(adv), row)).unwrap(),
-            Code::Disadvantage => write!(self.surface, "{}{disadv}", Goto(centre(disadv), row)).unwrap(),
-            Code::Percentile => write!(self.surface, "{}{percent}", Goto(centre(percent), row)).unwrap(),
-            Code::Normal => row -= 1, // compensation for not needing the extra space for a code print
-        }
+        row -= 1; // back onto the last command
         self.surface.flush().unwrap();
 
         // little loading animation
@@ -178,115 +474,275 @@ impl Table {
             self.surface.flush().unwrap();
         }
         
+        // skill shot stands in for the key press
+        if self.options.skill_shot {
+            self.skill_shot(row + 1, events);
+            return;
+        }
+
         // press to continue
         let msg = "Press any key to roll";
         write!(self.surface, "{}{msg}", Goto(centre(msg), row + 1)).unwrap();
         self.surface.flush().unwrap();
-        press_to_continue();
+        match self.options.time_limit {
+            Some(seconds) => { // count down underneath a second a tick, and roll anyway if nobody presses anything
+                events.tick_every(Some(Duration::from_secs(1)));
+                let mut remaining = seconds;
+                while remaining > 0 {
+                    let countdown = format!(" Auto-roll in {remaining:>2} ");
+                    write!(self.surface, "{}{countdown}", Goto(centre(&countdown), row + 2)).unwrap();
+                    self.surface.flush().unwrap();
+                    match events.wait() {
+                        Event::Key(_) | Event::Closed => break,
+                        Event::Tick => remaining -= 1,
+                        Event::Die(_) => (),
+                    }
+                }
+                events.tick_every(None);
+            },
+            None => {
+                events.key();
+            },
+        }
+    }
+
+    fn skill_shot(&mut self, row: u16, events: &mut Events) { // sweeps a marker along a bar until a key is pressed, then adjusts the first command's modifier by how close to the centre it was
+        const HALF_WIDTH: u16 = 10; // bar is 21 columns, centre included
+        const SWEEPS: u16 = 6; // passes before the shot counts as a miss
+        const STEP_TIME: u64 = 30; // ms per column
+        let msg = "Press a key as the marker crosses the centre";
+        write!(self.surface, "{}{msg}", Goto(centre(msg), row)).unwrap();
+        let (col, _) = terminal_centre();
+        let left = col.saturating_sub(HALF_WIDTH).max(1);
+
+        // marker bounces back and forth across the bar, a column a tick
+        events.tick_every(Some(Duration::from_millis(STEP_TIME)));
+        let mut offset: Option<u16> = None; // distance from the centre when the key was pressed
+        'sweeping: for sweep in 0..SWEEPS {
+            for step in 0..=(2 * HALF_WIDTH) {
+                let position = if sweep % 2 == 0 { step } else { 2 * HALF_WIDTH - step };
+                let mut bar = String::new();
+                for i in 0..=(2 * HALF_WIDTH) {
+                    match i {
+                        _ if i == position => bar.push('o'),
+                        _ if i == HALF_WIDTH => bar.push('|'),
+                        _ => bar.push('-'),
+                    }
+                }
+                write!(self.surface, "{}{bar}", Goto(left, row + 2)).unwrap();
+                self.surface.flush().unwrap();
+                loop {
+                    match events.wait() {
+                        Event::Key(_) => {
+                            offset = Some(position.abs_diff(HALF_WIDTH));
+                            break 'sweeping;
+                        },
+                        Event::Closed => break 'sweeping,
+                        Event::Tick => break, // on to the next column
+                        Event::Die(_) => (),
+                    }
+                }
+            }
+        }
+        events.tick_every(None);
+
+        // score the shot
+        let bonus: i16 = match offset {
+            Some(0) => 1,
+            Some(1..=2) => 0,
+            _ => -1, // too far out, or never pressed
+        };
+        let verdict = match bonus {
+            1 => " Bullseye! +1 ",
+            0 => " Close enough: +0 ",
+            _ => " Missed: -1 ",
+        };
+        write!(self.surface, "{}{verdict}", Goto(centre(verdict), row + 4)).unwrap();
+        self.surface.flush().unwrap();
+        let command = &mut self.command_log[0];
+        if bonus != 0 && (command.modifier() + bonus).abs() <= 99 { // stays within the modifier limit
+            command.modifiers.push(Modifier { value: bonus, name: Some(String::from("skill")) });
+        }
+        thread::sleep(Duration::from_millis(800));
     }
 
-    pub fn show_math(&mut self) -> Result<(), &'static str> { // performs and shows calculations
+    pub fn show_math(&mut self) -> Result<(), DiceError> { // performs and shows calculations
         // do_math() is similar logic, but returns the calculations as a string instead of printing it in a graph
+        if let Some(ac) = self.options.horde {
+            return self.show_horde(ac);
+        }
 
         // safety
         let (max_cols, max_rows) = terminal_size().unwrap();
-        let height: u16 = (self.results.len() + self.command_log.len() + 7) as u16; // one row per result and command divider, plus 7 for header/footer/label
-        let width: u16 = 34; // graph width (window needs 36 cols -- clearing one extra col on either side)
+        let discarded: usize = self.discarded.keys().map(|id| self.crossed_out(*id).len()).sum();
+        let legend = match self.options.split {
+            _ if self.can_lock() => 2, // lock mode has two more key commands
+            Some(targets) => (targets as usize).saturating_sub(2).max(1), // split damage has one, and a row per target under the sum
+            None => 0,
+        };
+        let factors = self.command_log.iter().filter(|c| c.factor != Factor::One).count(); // multiplied/divided commands take an extra row
+        let steps = self.command_log.iter().filter(|c| c.clamp.is_set() || c.botch || c.glitch).count(); // as do clamped ones and pools that watch their 1s
+        let labels = self.command_log.iter().filter(|c| c.label.is_some()).count(); // so do labelled ones
+        let itemized: usize = self.command_log.iter().filter(|c| c.itemized()).map(|c| c.modifiers.len()).sum(); // and listed modifiers, one each
+        let narrated = self.narration.narrate(&self.results(), self.total()); // and the narration, under the rest
+        let height: u16 = (self.results.len() + discarded + self.command_log.len() + factors + steps + labels + itemized + legend + narrated.iter().count() + 7) as u16; // one row per result (rerolled faces included) and command divider, plus 7 for header/footer/label
+        let width: u16 = 36; // graph width (window needs 38 cols -- clearing one extra col on either side)
         if max_rows < height || max_cols < width + 2 {
-            return Err(" Window too small to display results ");
+            return Err(DiceError::TerminalTooSmall { needed: (width + 2, height) });
         }
 
         // setup
         let mut graph = Graph::new(height as usize);
         graph.clear_area(&mut self.surface);
-        let mut results = self.results.clone().drain().collect::<Vec<(usize, u16)>>();
-        results.sort_by_key(|k| k.0);
         self.graph_on = true;
         self.error_on = false;
 
         // header
-        match self.code {
-            Code::Advantage => graph.print_header(&mut self.surface, "Advantage roll"),
-            Code::Disadvantage => graph.print_header(&mut self.surface, "Disadvantage roll"),
-            Code::Percentile => graph.print_header(&mut self.surface, "Percentile roll"),
-            Code::Normal => graph.print_header(&mut self.surface, "Normal roll"),
-        }
-
-        // draw graph depending on code
-        match self.code {
-            Code::Advantage | Code::Disadvantage => {
-                let command = self.command_log.iter().next().unwrap();
-                let (_, kind, modifier) = get_command_values(command).unwrap();
-                graph.print_command(&mut self.surface, command);
-                
-                let selected: u16; // which of the two die is chosen
-                match self.code {
-                    Code::Advantage => selected = self.advantage().expect("Should have been able to assess advantage"),
-                    Code::Disadvantage => selected = self.disadvantage().expect("Should have been able to assess disadvantage"),
-                    _ => selected = 0,
-                }
+        let header = match self.shared_code() {
+            Some(code) => format!("{code} roll{}", self.versus_label()),
+            None => format!("Mixed roll{}", self.versus_label()),
+        };
+        graph.print_header(&mut self.surface, &header);
 
-                for (line, (_, result)) in results.drain(..).enumerate() {
-                    graph.goto_result_line(&mut self.surface, line);
-                    let result_format: String;
-                    match result {
-                        20 if selected == 20 && kind == D::Twenty => result_format = format!("{}{result}{}", color::Fg(color::Green), color::Fg(color::Reset)),
-                        1 if selected == 1 && kind == D::Twenty => result_format = format!("{}{result}{}", color::Fg(color::Red), color::Fg(color::Reset)),
-                        _ => result_format = format!("{result}"),
-                    }
-                    write!(self.surface, "{result_format}").unwrap();
-                }
+        // draw each command depending on its code
+        for (group, command) in self.command_log.iter().enumerate() {
+            let Command { code, kind, keep, target, factor, clamp, .. } = *command;
+            let modifier = command.modifier();
+            let mut shown = command.clone();
+            shown.code = Code::Normal; // the greyed-out throws show how it was rolled, and the column has room for little more than the dice
+            if command.itemized() {
+                shown.modifiers.clear(); // they get rows of their own
+            }
+            let shown = format!("{}{shown}{}", self.group_colour(group), color::Fg(color::Reset));
+            graph.print_command(&mut self.surface, &shown);
 
-                graph.running_row += 2;
-                graph.print_totals(&mut self.surface, selected, modifier);
-            },
-            Code::Percentile => {
-                let command = self.command_log.iter().next().unwrap();
-                let (.., modifier) = get_command_values(command).unwrap();
-                let sum = self.percent_sum().expect("Should have been able to assess");
-                graph.print_command(&mut self.surface, command);
-
-                for (line, (_, result)) in results.drain(..).enumerate() {
-                    graph.goto_result_line(&mut self.surface, line);
-                    let mut result_format = String::from(result.to_string());
-                    if line == 0 && result == 0 { // (this works because the tens-place die always rolls first)
-                        result_format.push('0'); // push the extra zero onto the tens die if it's zero
+            let mut line = 0;
+            match code {
+                Code::Advantage(throws) | Code::Disadvantage(throws) => {
+                    let selected = self.selected_throw(group); // which of the throws is chosen
+                    for throw in 0..throws as usize {
+                        let throw_results = self.throw_results(group, throw);
+                        let faces: Vec<i16> = throw_results.iter().map(|(_, face)| kind.score(*face)).collect();
+                        for ((id, result), kept) in throw_results.iter().zip(keep.kept(&faces)) {
+                            graph.goto_result_line(&mut self.surface, line);
+                            let label = self.kinds.get(id).unwrap().label(*result); // the tens die shows "00" for zero
+                            let result_format = match result {
+                                _ if throw != selected || !kept => format!("{}{label}{}", color::Fg(color::LightBlack), color::Fg(color::Reset)), // the throw not taken is greyed out
+                                20 if kind == D::Twenty => format!("{}{label}{}", color::Fg(color::Green), color::Fg(color::Reset)),
+                                1 if kind == D::Twenty => format!("{}{label}{}", color::Fg(color::Red), color::Fg(color::Reset)),
+                                _ => label,
+                            };
+                            write!(self.surface, "{result_format}").unwrap();
+                            line += 1;
+                        }
                     }
-                    write!(self.surface, "{result_format}").unwrap();
-                }
-                
-                graph.running_row += 2;
-                graph.print_totals(&mut self.surface, sum, modifier);
-            },
-            Code::Normal => {
-                for command in self.command_log.iter() {
-                    let (coefficient, kind, modifier) = get_command_values(command).unwrap();
-                    let mut running_total = 0; // i.e. the result total for a specific command, before modifiers
-                    graph.print_command(&mut self.surface, command);
+                },
+                Code::Wild => {
+                    let selected = self.selected_throw(group); // the trait die or the wild die, whichever came to more
+                    for throw in 0..code.throws() {
+                        for (id, result) in self.throw_results(group, throw) {
+                            graph.goto_result_line(&mut self.surface, line);
+                            let label = kind.label(result);
+                            let result_format = match throw {
+                                _ if throw != selected => format!("{}{label}{}", color::Fg(color::LightBlack), color::Fg(color::Reset)),
+                                1 => format!("{}{label}{}", color::Fg(color::Cyan), color::Fg(color::Reset)), // the wild die
+                                _ => label,
+                            };
+                            write!(self.surface, "{result_format}").unwrap();
+                            if self.exploded.contains_key(&id) {
+                                write!(self.surface, "!").unwrap();
+                            }
+                            line += 1;
+                        }
+                    }
+                },
+                Code::Percentile => {
+                    for (id, result) in self.group_results(group) {
+                        graph.goto_result_line(&mut self.surface, line);
+                        let result_format = self.kinds.get(&id).unwrap().label(result); // the tens die shows "00" for zero
+                        write!(self.surface, "{result_format}").unwrap();
+                        line += 1;
+                    }
+                },
+                Code::Normal => {
+                    let results = self.group_results(group);
+                    let faces: Vec<i16> = results.iter().map(|(_, face)| kind.score(*face)).collect();
+                    for ((id, result), kept) in results.iter().zip(keep.kept(&faces)) {
+                        for crossed in self.crossed_out(*id) { // rerolled faces go above the one that replaced them
+                            graph.goto_result_line(&mut self.surface, line);
+                            write!(self.surface, "{crossed}").unwrap();
+                            line += 1;
+                        }
 
-                    for (line, (_, result)) in results.drain(..coefficient as usize).enumerate() {
                         let result_format: String; // with colour embedded
+                        let label = kind.label(*result);
                         match result {
-                            20 if kind == D::Twenty => result_format = format!("{}{result}{}", color::Fg(color::Green), color::Fg(color::Reset)),
-                            1 if kind == D::Twenty => result_format = format!("{}{result}{}", color::Fg(color::Red), color::Fg(color::Reset)),
-                            _ => result_format = format!("{result}"),
+                            _ if !kept => result_format = format!("{}{label}{}", color::Fg(color::LightBlack), color::Fg(color::Reset)), // dropped dice are greyed out
+                            _ if keep == Keep::Middle => result_format = format!("{}{label}{}", color::Fg(color::Yellow), color::Fg(color::Reset)), // the median stands out
+                            1 if command.botch || command.glitch => result_format = format!("{}{label}{}", color::Fg(color::Magenta), color::Fg(color::Reset)), // 1s cancel successes or glitch
+                            _ if target.is_some_and(|t| t.passes(kind.score(*result))) => result_format = format!("{}{label}{}", color::Fg(color::Green), color::Fg(color::Reset)), // pool successes
+                            _ if target.is_some() => result_format = format!("{}{label}{}", color::Fg(color::Red), color::Fg(color::Reset)), // pool failures
+                            20 if kind == D::Twenty => result_format = format!("{}{label}{}", color::Fg(color::Green), color::Fg(color::Reset)),
+                            1 if kind == D::Twenty => result_format = format!("{}{label}{}", color::Fg(color::Red), color::Fg(color::Reset)),
+                            _ => result_format = label,
                         }
 
                         graph.goto_result_line(&mut self.surface, line);
                         write!(self.surface, "{result_format}").unwrap();
-
-                        running_total += result;
+                        if self.exploded.contains_key(id) { // mark dice that set off the next one down
+                            write!(self.surface, "!").unwrap();
+                        }
+                        if self.locked.contains(id) {
+                            write!(self.surface, "*").unwrap();
+                        }
+                        line += 1;
                     }
+                },
+            }
 
-                    graph.command_row += coefficient + 1; // skip rows after printing command & results, to set up where the next command will be
-                    graph.running_row += coefficient; // skip rows *before* printing totals/modifier
-                    graph.print_totals(&mut self.surface, running_total, modifier);
+            if let Some(label) = &command.label { // goes under the results, beside the totals
+                graph.print_label(&mut self.surface, label, line);
+                line += 1;
+            }
+
+            graph.command_row += line as u16 + 1; // skip rows after printing command & results, to set up where the next command will be
+            graph.running_row += line as u16; // skip rows *before* printing totals/modifier
+            if let Some(pool) = self.pool_result(group) { // successes beside what the 1s did
+                graph.print_step(&mut self.surface, pool.successes, &pool);
+                graph.command_row += 1;
+            }
+            let faces: Vec<i16> = self.group_results(group).iter().map(|(_, face)| kind.score(*face)).collect();
+            if factor != Factor::One {
+                graph.print_step(&mut self.surface, command.total(&faces), &factor);
+                graph.command_row += 1;
+            }
+            if clamp.is_set() { // raw total beside the bounds, then the clamped value on the totals line
+                graph.print_step(&mut self.surface, factor.apply(command.total(&faces)), &clamp);
+                graph.command_row += 1;
+            }
+            if command.itemized() {
+                for modifier in command.modifiers.iter() {
+                    graph.print_modifier(&mut self.surface, modifier);
+                    graph.command_row += 1;
                 }
-        
-                // print sum of all commands at the bottom
-                let final_sum = self.full_sum().expect("Should have been able to sum results");
-                write!(self.surface, "{}= {final_sum}", Goto(graph.sum_col - 2, graph.running_row + 1)).unwrap();
+            }
+            let total = self.throw_total(group, self.selected_throw(group)).expect("Should have been able to total the command");
+            graph.print_totals(&mut self.surface, total, modifier);
+        }
+
+        // print sum of all commands at the bottom
+        let final_sum = self.full_sum().expect("Should have been able to sum results");
+        match (self.check(), self.fate_ladder()) {
+            (Some(check), _) => { // a check cares about the margin more than the sum
+                let sum_line = format!("= {final_sum} {check}");
+                let colour = if check.made { color::Fg(color::Green).to_string() } else { color::Fg(color::Red).to_string() };
+                write!(self.surface, "{}{colour}{sum_line}{}", Goto(graph.sum_col + 4 - sum_line.len() as u16, graph.running_row + 1), color::Fg(color::Reset)).unwrap(); // right-aligned like the ladder
             },
+            (None, Some(adjective)) => {
+                let sum_line = format!("= {final_sum} {adjective}");
+                write!(self.surface, "{}{sum_line}", Goto(graph.sum_col + 4 - sum_line.len() as u16, graph.running_row + 1)).unwrap(); // right-aligned to the divider so the adjective fits
+            },
+            (None, None) => write!(self.surface, "{}= {final_sum}", Goto(graph.sum_col - 2, graph.running_row + 1)).unwrap(),
         }
 
         // print key commands
@@ -295,111 +751,285 @@ impl Table {
             Goto(graph.command_col, graph.running_row + 2),
             Goto(graph.command_col, graph.running_row + 3),
         ).unwrap();
+        if self.can_lock() {
+            write!(self.surface, "{}1-{}: Lock/unlock die{}e: Reroll unlocked ({} left) ",
+                Goto(graph.command_col, graph.running_row + 4),
+                self.kinds.len(),
+                Goto(graph.command_col, graph.running_row + 5),
+                self.rerolls_left(),
+            ).unwrap();
+        }
+        if self.can_split() {
+            write!(self.surface, "{}1-{}: Toggle save", Goto(graph.command_col, graph.running_row + 4), self.split_damage().len()).unwrap();
+            for (target, damage, saved) in self.split_damage() { // right-aligned under the sum
+                let row = format!("T{target}: {damage}{}", if saved { " save" } else { "" });
+                write!(self.surface, "{}{row}", Goto(graph.sum_col + 4 - row.len() as u16, graph.running_row + 1 + target)).unwrap();
+            }
+        }
+        if let Some(line) = narrated {
+            let line: String = line.chars().take(max_cols as usize).collect();
+            write!(self.surface, "{}{line}", Goto(centre(&line), graph.running_row + 4 + legend as u16)).unwrap();
+        }
 
         self.surface.flush().unwrap();
         Ok(())
     }
 
-    pub fn do_math(&mut self) -> String {
+    fn show_horde(&mut self, ac: u16) -> Result<(), DiceError> { // a horde gets one row per attacker instead of the working for every roll
+        let attacks = self.horde_attacks();
+
+        // safety
+        let (max_cols, max_rows) = terminal_size().unwrap();
+        let height: u16 = (attacks.len() + 7) as u16; // one row per attacker, plus 7 for header/footer/label
+        let width: u16 = 36;
+        if max_rows < height || max_cols < width + 2 {
+            return Err(DiceError::TerminalTooSmall { needed: (width + 2, height) });
+        }
+
+        // setup
+        let mut graph = Graph::new(height as usize);
+        graph.clear_area(&mut self.surface);
+        self.graph_on = true;
+        self.error_on = false;
+        graph.print_columns(&mut self.surface, &format!("Horde vs AC {ac}"), "Atk   Roll  Total  Result  Dmg");
+
+        // one row per attacker
+        for (attacker, attack) in attacks.iter().enumerate() {
+            graph.print_row(&mut self.surface, &format!("#{:<3}{attack}", attacker + 1));
+        }
+
+        // hits and damage right-aligned under the divider, crits below
+        let hits = attacks.iter().filter(|attack| attack.hit).count();
+        let crits = attacks.iter().filter(|attack| attack.crit).count();
+        let damage: i16 = attacks.iter().map(|attack| attack.damage).sum();
+        let sum_line = format!("Hits {hits}/{} = {damage}", attacks.len());
+        let crit_line = format!("Crits {crits}");
+        write!(self.surface, "{}{sum_line}{}{crit_line}{}t: Toggle display{}r: Make another roll{}esc: Exit",
+            Goto(graph.sum_col + 4 - sum_line.len() as u16, graph.running_row + 1),
+            Goto(graph.sum_col + 4 - crit_line.len() as u16, graph.running_row + 2),
+            Goto(graph.command_col, graph.running_row + 1),
+            Goto(graph.command_col, graph.running_row + 2),
+            Goto(graph.command_col, graph.running_row + 3),
+        ).unwrap();
+
+        self.surface.flush().unwrap();
+        Ok(())
+    }
+
+    pub fn total(&self) -> i16 { // the throw as one number: the damage dealt for a horde, otherwise the sum
+        match self.options.horde {
+            Some(_) => self.horde_attacks().iter().map(|attack| attack.damage).sum(),
+            None => self.full_sum().expect("Should have been able to sum results"),
+        }
+    }
+
+    pub fn results(&self) -> Vec<CommandResult> { // how each command came out, worked out from the dice as they lie
+        self.command_log.iter().enumerate()
+            .map(|(group, command)| {
+                let throws: Vec<Vec<(usize, u16)>> = (0..command.code.throws()).map(|throw| self.throw_results(group, throw)).collect();
+                let mut result = CommandResult::new(command, throws.iter().map(|faces| faces.iter().map(|(id, face)| (self.kinds[id], *face)).collect()).collect());
+                result.rerolled = throws.iter().enumerate()
+                    .flat_map(|(throw, faces)| faces.iter().enumerate().flat_map(move |(die, (id, face))| {
+                        Rerolled::chain(throw, die, self.kinds[id], self.discarded.get(id).map_or(&[][..], |faces| faces), *face)
+                    }))
+                    .collect();
+                result
+            })
+            .collect()
+    }
+
+    pub fn result(&self) -> RollResult { // the whole throw as it lies, summed up the same way as a throw without the table
+        let commands = self.results();
+        let (line, total) = match self.options.horde {
+            Some(_) => (self.horde_line(), self.total()),
+            None => headless::describe(&self.options, &commands),
+        };
+        RollResult { commands, line, total }
+    }
+
+    fn horde_line(&self) -> String { // hits, crits, and the damage they come to
+        let attacks = self.horde_attacks();
+        let hits = attacks.iter().filter(|attack| attack.hit).count();
+        let crits = attacks.iter().filter(|attack| attack.crit).count();
+        let damage: i16 = attacks.iter().map(|attack| attack.damage).sum();
+        format!("{hits}/{} hits ({crits} crit{}) => {damage}", attacks.len(), if crits == 1 { "" } else { "s" })
+    }
+
+    pub fn do_math(&mut self) -> String { // the result line as the prompt shows it, with each die (coloured) and the sum worked out
 
         // setup
         let mut one_liner = String::new(); // return value
-        let mut results = self.results.clone().drain().collect::<Vec<(usize, u16)>>();
-        results.sort_by_key(|k| k.0);
-
-        match self.code {
-            Code::Advantage | Code::Disadvantage => {
-                let command = self.command_log.iter().next().unwrap();
-                let (_, kind, modifier) = get_command_values(command).unwrap();
-                let selected: u16; // which of the two die is chosen
-
-                match self.code {
-                    Code::Advantage => selected = self.advantage().expect("Should have been able to assess advantage"),
-                    Code::Disadvantage => selected = self.disadvantage().expect("Should have been able to assess disadvantage"),
-                    _ => selected = 0,
-                }
 
-                for (line, (_, result)) in results.drain(..).enumerate() {
-                    let result_format: String;
-                    match result {
-                        20 if selected == 20 && kind == D::Twenty => result_format = format!("{}{result}{}", color::Fg(color::Green), color::Fg(color::Reset)),
-                        1 if selected == 1 && kind == D::Twenty => result_format = format!("{}{result}{}", color::Fg(color::Red), color::Fg(color::Reset)),
-                        _ => result_format = format!("{result}"),
-                    }
-                    match line {
-                        0 => one_liner.push_str(&result_format),
-                        1 => one_liner.push_str(&format!(" | {result_format}")),
-                        _ => (),
+        // character generation lists the ability scores instead, and a horde sums up its hits
+        if self.options.genchar || self.options.horde.is_some() {
+            return self.result().line;
+        }
+
+        // if there was only one command, show its working before the sum
+        if let [command] = &self.command_log[..] {
+            let Command { code, kind, keep, target, factor, clamp, .. } = *command;
+            let modifier = command.modifier();
+            if let Some(label) = &command.label {
+                one_liner.push_str(&format!("{label}: "));
+            }
+            match code {
+                Code::Advantage(throws) | Code::Disadvantage(throws) => {
+                    let selected = self.selected_throw(0); // which of the throws is chosen
+                    for throw in 0..throws as usize {
+                        let throw_results = self.throw_results(0, throw);
+                        let total = self.throw_total(0, throw).expect("Should have been able to total the throw");
+                        let label = match throw_results[..] {
+                            [(_, result)] => kind.label(result), // a single die shows its face
+                            _ => total.to_string(), // bigger throws (and d100s) show what they came to
+                        };
+                        let result_format = match throw_results[..] {
+                            _ if throw != selected => format!("{}{label}{}", color::Fg(color::LightBlack), color::Fg(color::Reset)),
+                            [(_, 20)] if kind == D::Twenty => format!("{}{label}{}", color::Fg(color::Green), color::Fg(color::Reset)),
+                            [(_, 1)] if kind == D::Twenty => format!("{}{label}{}", color::Fg(color::Red), color::Fg(color::Reset)),
+                            _ => label,
+                        };
+                        match throw {
+                            0 => one_liner.push_str(&result_format),
+                            _ => one_liner.push_str(&format!(" | {result_format}")),
+                        }
                     }
-                }
 
-                match modifier >= 0 {
-                    true => one_liner.push_str(&format!(" => {selected} + {modifier} = {}", selected as i16 + modifier)),
-                    false => one_liner.push_str(&format!(" => {selected} - {} = {}", modifier.abs(), selected as i16 + modifier)),
-                }
-            },
-            Code::Percentile => {
-                let command = self.command_log.iter().next().unwrap();
-                let (.., modifier) = get_command_values(command).unwrap();
-                let sum = self.percent_sum().expect("Should have been able to assess percentage");
-
-                for (line, (_, result)) in results.drain(..).enumerate() {
-                    let mut result_format = String::from(result.to_string());
-                    if line == 0 && result == 0 { // (this works because the tens-place die always rolls first)
-                        result_format.push('0'); // push the extra zero onto the tens die if it's zero
+                    let selected = self.throw_total(0, selected).expect("Should have been able to total the throw");
+                    match modifier >= 0 {
+                        true => one_liner.push_str(&format!(" => {selected} + {modifier} = ")),
+                        false => one_liner.push_str(&format!(" => {selected} - {} = ", modifier.abs())),
                     }
-                    match line {
-                        0 => one_liner.push_str(&result_format),
-                        1 => one_liner.push_str(&format!(", {result_format}")),
-                        _ => (),
+                },
+                Code::Wild => {
+                    let selected = self.selected_throw(0);
+                    for throw in 0..code.throws() {
+                        let chain: Vec<String> = self.throw_results(0, throw).iter()
+                            .map(|(id, result)| format!("{}{}", kind.label(*result), if self.exploded.contains_key(id) { "!" } else { "" }))
+                            .collect();
+                        let chain = chain.join(" + "); // aces add up
+                        let result_format = match throw {
+                            _ if throw != selected => format!("{}{chain}{}", color::Fg(color::LightBlack), color::Fg(color::Reset)),
+                            1 => format!("{}{chain}{}", color::Fg(color::Cyan), color::Fg(color::Reset)),
+                            _ => chain,
+                        };
+                        match throw {
+                            0 => one_liner.push_str(&result_format),
+                            _ => one_liner.push_str(&format!(" | {result_format}")),
+                        }
                     }
-                }
-                
-                match modifier >= 0 {
-                    true => one_liner.push_str(&format!(" => {sum} + {modifier} = {}", sum as i16 + modifier)),
-                    false => one_liner.push_str(&format!(" => {sum} - {} = {}", modifier.abs(), sum as i16 + modifier)),
-                }
-            },
-            Code::Normal => {
-                for command in self.command_log.iter() {
-                    let (coefficient, kind, modifier) = get_command_values(command).unwrap();
-                    let mut running_total = 0; // i.e. the result total for a specific command, before modifiers
 
-                    for (line, (_, result)) in results.drain(..coefficient as usize).enumerate() {
+                    let selected = self.throw_total(0, selected).expect("Should have been able to total the throw");
+                    match modifier >= 0 {
+                        true => one_liner.push_str(&format!(" => {selected} + {modifier} = ")),
+                        false => one_liner.push_str(&format!(" => {selected} - {} = ", modifier.abs())),
+                    }
+                },
+                Code::Percentile => {
+                    let sum = self.percent_sum(0, 0).expect("Should have been able to assess percentage");
+
+                    for (line, (id, result)) in self.group_results(0).into_iter().enumerate() {
+                        let result_format = self.kinds.get(&id).unwrap().label(result); // the tens die shows "00" for zero
+                        match line {
+                            0 => one_liner.push_str(&result_format),
+                            _ => one_liner.push_str(&format!(", {result_format}")),
+                        }
+                    }
+                    
+                    match modifier >= 0 {
+                        true => one_liner.push_str(&format!(" => {sum} + {modifier} = ")),
+                        false => one_liner.push_str(&format!(" => {sum} - {} = ", modifier.abs())),
+                    }
+                },
+                Code::Normal => {
+                    let results = self.group_results(0);
+                    let faces: Vec<i16> = results.iter().map(|(_, face)| kind.score(*face)).collect();
+                    let running_total = command.total(&faces); // i.e. the result total (or success count) for a specific command, before modifiers
+                    for (line, ((id, result), kept)) in results.iter().zip(keep.kept(&faces)).enumerate() {
                         let result_format: String; // with colour embedded
+                        let label = match kind {
+                            D::Fudge => format!("[{}]", kind.label(*result)), // so blank faces still show up in the line
+                            _ => kind.label(*result),
+                        };
                         match result {
-                            20 if kind == D::Twenty => result_format = format!("{}{result}{}", color::Fg(color::Green), color::Fg(color::Reset)),
-                            1 if kind == D::Twenty => result_format = format!("{}{result}{}", color::Fg(color::Red), color::Fg(color::Reset)),
-                            _ => result_format = format!("{result}"),
+                            _ if !kept => result_format = format!("{}{label}{}", color::Fg(color::LightBlack), color::Fg(color::Reset)),
+                            _ if keep == Keep::Middle => result_format = format!("{}{label}{}", color::Fg(color::Yellow), color::Fg(color::Reset)),
+                            1 if command.botch || command.glitch => result_format = format!("{}{label}{}", color::Fg(color::Magenta), color::Fg(color::Reset)),
+                            _ if target.is_some_and(|t| t.passes(kind.score(*result))) => result_format = format!("{}{label}{}", color::Fg(color::Green), color::Fg(color::Reset)),
+                            _ if target.is_some() => result_format = format!("{}{label}{}", color::Fg(color::Red), color::Fg(color::Reset)),
+                            20 if kind == D::Twenty => result_format = format!("{}{label}{}", color::Fg(color::Green), color::Fg(color::Reset)),
+                            1 if kind == D::Twenty => result_format = format!("{}{label}{}", color::Fg(color::Red), color::Fg(color::Reset)),
+                            _ => result_format = label,
                         }
 
-                        running_total += result;
-
-                        // if there was only one command, insert individual roll results onto return, up to a maximum.
+                        // insert individual roll results onto return, up to a maximum.
                         // will actually display six results IF there are six, but if there are more than six, it will display five then an ellipsis.
+                        let mark = if self.exploded.contains_key(id) { "!" } else { "" }; // marks dice that set off another
+                        let crossed: String = self.crossed_out(*id).iter().map(|face| format!("{face} ")).collect(); // rerolled faces
+                        let separator = match (kind, target) {
+                            (_, Some(_)) => ", ", // pool dice aren't added together
+                            (D::Fudge | D::Coin, None) => " ",
+                            _ => " + ",
+                        };
                         match line {
-                            0 if self.command_log.len() == 1 => one_liner.push_str(&result_format),
-                            1..=DISPLAY_RESULTS if self.command_log.len() == 1 => {
-                                if line == DISPLAY_RESULTS && coefficient as usize > (DISPLAY_RESULTS + 1) {
-                                    one_liner.push_str(&format!(" + ..."));
+                            0 => one_liner.push_str(&format!("{crossed}{result_format}{mark}")),
+                            1..=DISPLAY_RESULTS => {
+                                if line == DISPLAY_RESULTS && faces.len() > (DISPLAY_RESULTS + 1) {
+                                    one_liner.push_str(&format!("{separator}..."));
                                 } else {
-                                    one_liner.push_str(&format!(" + {result_format}"));
+                                    one_liner.push_str(&format!("{separator}{crossed}{result_format}{mark}"));
                                 }
                             },
                             _ => (),
                         }
                     }
 
-                    match modifier >= 0 {
-                        true if self.command_log.len() == 1 => one_liner.push_str(&format!(" + {modifier} = {running_total} + {modifier} = ")),
-                        false if self.command_log.len() == 1 => one_liner.push_str(&format!(" - {} = {running_total} - {} = ", modifier.abs(), modifier.abs())),
-                        _ => (),
+                    let mut steps = Table::steps(factor, clamp); // e.g. " x 10" or " min 8"
+                    let running_total = match self.pool_result(0) { // pools that watch their 1s show their successes before any come off
+                        Some(pool) => {
+                            steps.insert_str(0, &format!(" {pool}"));
+                            pool.successes
+                        },
+                        None => running_total,
+                    };
+                    let counted = if command.glitch { "hits" } else { "successes" };
+                    match (target, modifier >= 0) {
+                        (Some(_), true) => one_liner.push_str(&format!(" => {running_total} {counted}{steps} + {modifier} = ")),
+                        (Some(_), false) => one_liner.push_str(&format!(" => {running_total} {counted}{steps} - {} = ", modifier.abs())),
+                        (None, true) if !steps.is_empty() => one_liner.push_str(&format!(" = {running_total}{steps} + {modifier} = ")),
+                        (None, false) if !steps.is_empty() => one_liner.push_str(&format!(" = {running_total}{steps} - {} = ", modifier.abs())),
+                        (None, true) => one_liner.push_str(&format!(" + {modifier} = {running_total} + {modifier} = ")),
+                        (None, false) => one_liner.push_str(&format!(" - {} = {running_total} - {} = ", modifier.abs(), modifier.abs())),
                     }
+                },
+            }
+        }
+
+        // otherwise, labelled commands (and every command in a burst) get their own subtotals
+        if self.command_log.len() > 1 && (self.options.burst || self.command_log.iter().any(|command| command.label.is_some())) {
+            for (group, command) in self.command_log.iter().enumerate() {
+                let subtotal = self.throw_total(group, self.selected_throw(group)).expect("Should have been able to total the command") + command.modifier();
+                match &command.label {
+                    Some(label) => one_liner.push_str(&format!("{label}: {subtotal}, ")),
+                    None => one_liner.push_str(&format!("{command}: {subtotal}, ")),
                 }
-        
-                let final_sum = self.full_sum().expect("Should have been able to sum results");
-                one_liner.push_str(&final_sum.to_string());
-            },
+            }
+            one_liner.truncate(one_liner.len() - 2); // last separator
+            one_liner.push_str(" => ");
+        }
+
+        let final_sum = self.full_sum().expect("Should have been able to sum results");
+        one_liner.push_str(&final_sum.to_string());
+        if let Some(adjective) = self.fate_ladder() {
+            one_liner.push_str(&format!(" ({adjective})"));
+        }
+        if let Some(check) = self.check() {
+            one_liner.push_str(&format!("{} ({check})", self.versus_label()));
+        }
+        if self.can_split() {
+            let targets: Vec<String> = self.split_damage().iter()
+                .map(|(target, damage, saved)| format!("T{target} {damage}{}", if *saved { " save" } else { "" }))
+                .collect();
+            one_liner.push_str(&format!(" ({})", targets.join(", ")));
         }
         one_liner
     }
@@ -447,10 +1077,10 @@ impl Table {
         self.surface.flush().unwrap();
     }
 
-    pub fn print_error(&mut self, error: &'static str) {
+    pub fn print_error(&mut self, error: DiceError) {
         let (_, middle) = terminal_centre();
         let help = " Resize and press 't' to try again, \n or 'r' to return to command line ";
-        let print: String = format!("{error}\n{help}");
+        let print: String = format!(" {error} \n{help}");
         let offset = print.lines().count() as u16 / 2;
         for (n, line) in print.lines().enumerate() {
             write!(self.surface, "{}{line}", Goto(centre(line), middle.checked_sub(offset).unwrap_or(1) + n as u16)).unwrap();
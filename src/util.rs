@@ -1,37 +1,38 @@
-use std::io::stdin;
-
-use termion::input::TermRead;
+#[cfg(feature = "tui")]
 use termion::terminal_size;
 
-use crate::die::D;
 
 // utility functions
 
-pub fn press_to_continue() { // suspends program while waiting for user to press a key
-    let input = stdin();
-    for key in input.keys() {
-        match key.unwrap() {
-            _ => break,
-        }
+pub fn strip_prefix_ignore_case<'a>(input: &'a str, prefix: &str) -> Option<&'a str> { // like str::strip_prefix, but "Lock" matches "lock"
+    match input.get(..prefix.len()) {
+        Some(start) if start.eq_ignore_ascii_case(prefix) => Some(&input[prefix.len()..]),
+        _ => None,
+    }
+}
+
+pub fn strip_word_ignore_case<'a>(input: &'a str, word: &str) -> Option<&'a str> { // likewise, but only as a whole word: "skill d20" matches "skill", "skillful" doesn't
+    strip_prefix_ignore_case(input, word).filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+}
+
+pub fn strip_suffix_ignore_case<'a>(input: &'a str, suffix: &str) -> Option<&'a str> { // likewise for str::strip_suffix
+    let start = input.len().checked_sub(suffix.len())?;
+    match input.get(start..) {
+        Some(end) if end.eq_ignore_ascii_case(suffix) => Some(&input[..start]),
+        _ => None,
     }
 }
 
+#[cfg(feature = "tui")]
 pub fn centre(msg: &str) -> u16 { // returns a column value that will make a message centred in the terminal
     let (col, _) = terminal_centre();
     col.checked_sub(msg.len() as u16 / 2).unwrap_or(1)
 }
 
+#[cfg(feature = "tui")]
 pub fn terminal_centre() -> (u16, u16) {
     let (width, height) = terminal_size().unwrap();
     let col = width / 2;
     let row = height / 2;
     (col, row)
 }
-
-pub fn is_two_digits(face: u16, kind: D) -> bool {
-    if face >= 10 || kind == D::PercentTens {
-        true
-    } else {
-        false
-    }
-}
\ No newline at end of file
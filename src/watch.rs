@@ -0,0 +1,75 @@
+use crate::error::DiceError;
+use crate::headless;
+use crate::input_handling::{generate_dice, get_options};
+
+use std::fs;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+// rolls the expressions written into a notes file, e.g. "I swing [[d20+5]]", each time it's saved.
+// results go in a comment right after the expression, which is also how rolled ones are told from new ones
+
+const POLL_TIME: u64 = 1000; // ms between checks for a save
+const MARK: &str = "<!-- rolled:";
+
+pub fn watch(path: &str, open: &str, close: &str) -> Result<(), &'static str> { // checks the file until the program is closed, rolling anything new after each save
+    let mut last_seen: Option<SystemTime> = None;
+    loop {
+        let modified = fs::metadata(path).and_then(|file| file.modified()).map_err(|_| "Could not read the watched file")?;
+        if last_seen != Some(modified) {
+            let text = fs::read_to_string(path).map_err(|_| "Could not read the watched file")?;
+            if let Some(rolled) = roll_new(&text, open, close) {
+                fs::write(path, rolled).map_err(|_| "Could not write to the watched file")?;
+            }
+            last_seen = fs::metadata(path).and_then(|file| file.modified()).ok(); // our own write isn't a new save
+        }
+        thread::sleep(Duration::from_millis(POLL_TIME));
+    }
+}
+
+fn roll_new(text: &str, open: &str, close: &str) -> Option<String> { // the text with a result after every expression that hasn't got one yet, or None if there were none
+    let mut rolled = String::new();
+    let mut rest = text;
+    let mut found = false;
+    while let Some(start) = rest.find(open) {
+        let after_open = &rest[start + open.len()..];
+        let Some(end) = after_open.find(close) else { break };
+        let expression = &after_open[..end];
+        let after = &after_open[end + close.len()..];
+        rolled.push_str(&rest[..start + open.len() + end + close.len()]);
+        if !after.trim_start_matches(' ').starts_with(MARK) && !expression.trim().is_empty() {
+            let result = roll(expression).unwrap_or_else(|error| format!("error, {error}"));
+            println!("{result}");
+            rolled.push_str(&format!(" {MARK} {result} -->"));
+            found = true;
+        }
+        rest = after;
+    }
+    rolled.push_str(rest);
+    found.then_some(rolled)
+}
+
+fn roll(expression: &str) -> Result<String, DiceError> {
+    let (options, input) = get_options(expression)?;
+    let (_, log) = generate_dice(input, &options)?;
+    let (result, _) = headless::throw(&log, &options)?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rolls_new_expressions() {
+        let rolled = roll_new("I swing [[d1+5]] and deal [[2d1]].", "[[", "]]").expect("there were new expressions");
+        assert_eq!(rolled, "I swing [[d1+5]] <!-- rolled: 1d1+5: 6 => 6 --> and deal [[2d1]] <!-- rolled: 2d1: 2 => 2 -->.");
+        assert_eq!(roll_new(&rolled, "[[", "]]"), None); // already rolled
+    }
+
+    #[test]
+    fn leaves_the_rest() {
+        assert_eq!(roll_new("no dice here, [[ ]] or [[d6", "[[", "]]"), None);
+        assert_eq!(roll_new("{d1x}", "{", "}").as_deref(), Some("{d1x} <!-- rolled: error, Die type error: 'd1x' -->"));
+    }
+}
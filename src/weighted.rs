@@ -0,0 +1,118 @@
+use crate::D;
+use crate::error::DiceError;
+
+use std::sync::Mutex;
+
+use rand::Rng;
+
+// dice whose faces come up at odds of their own, for homebrew tables or testing, defined by name, e.g. from a config line
+//     weighted loot = 1, 1, 2, 6
+// is a four-sided die that shows a 4 six times as often as a 1, thrown like any other die: "2dloot+1".
+// they're kept for the whole program, and D::Weighted holds which one it is
+
+struct Weighted {
+    name: String,
+    weights: Vec<u32>, // the odds of each face, 1 up
+}
+
+static DICE: Mutex<Vec<Weighted>> = Mutex::new(Vec::new());
+
+pub fn define(name: &str, weights: &str) -> Result<D, DiceError> { // keeps a die under the name (replacing one already called that), from its faces' weights separated by commas or spaces
+    const NAME_LIMIT: usize = 8; // fits the graph's command column with a coefficient
+    let name = name.trim().to_lowercase();
+    if name.len() < 2 || name.len() > NAME_LIMIT || !name.chars().all(|c| c.is_ascii_lowercase()) { // one letter would be read as 'F' or 'C'
+        return Err(DiceError::Needs { what: "A weighted die's name", needs: "2 to 8 letters, e.g. 'loot'" });
+    }
+    let weights = weights.split([',', ' ']).filter(|weight| !weight.is_empty())
+        .map(|weight| weight.parse::<u32>())
+        .collect::<Result<Vec<u32>, _>>()
+        .map_err(|_| DiceError::Malformed { part: "Weight" })?;
+    if weights.is_empty() || weights.len() > u16::MAX as usize || weights.iter().try_fold(0u32, |sum, weight| sum.checked_add(*weight)).is_none_or(|sum| sum == 0) {
+        return Err(DiceError::Needs { what: "A weighted die", needs: "a weight for each face, at least one above 0, e.g. '1, 1, 2, 6'" });
+    }
+    let mut dice = DICE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let id = match dice.iter().position(|die| die.name == name) {
+        Some(id) => id,
+        None => {
+            dice.push(Weighted { name: name.clone(), weights: Vec::new() });
+            dice.len() - 1
+        },
+    };
+    dice[id].weights = weights;
+    Ok(D::Weighted(id as u16))
+}
+
+pub(crate) fn take(input: &str) -> Option<(D, String)> { // finds a weighted die's name after the 'd' of a (lowercased) command, returning the die with the command as if it were an ordinary die of as many sides, so the rest of it reads as usual
+    let dice = DICE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    input.match_indices('d')
+        .filter(|(at, _)| !input[..*at].ends_with(|c: char| c.is_ascii_alphabetic())) // the 'd' starts the die, not a word like "adv"
+        .find_map(|(at, _)| {
+            let after = &input[at + 1..];
+            let (id, die) = dice.iter().enumerate().filter(|(_, die)| after.starts_with(&die.name)).max_by_key(|(_, die)| die.name.len())?; // the name can run straight into a rule, e.g. "2dlootr1"
+            Some((D::Weighted(id as u16), format!("{}{}{}", &input[..at + 1], die.weights.len(), &after[die.name.len()..])))
+        })
+}
+
+pub(crate) fn flip<R: Rng + ?Sized>(id: u16, rng: &mut R) -> u16 { // a face, drawn at the die's odds
+    let dice = DICE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let weights = &dice[id as usize].weights;
+    let mut roll = rng.gen_range(0..weights.iter().sum::<u32>());
+    for (face, weight) in weights.iter().enumerate() {
+        if roll < *weight {
+            return face as u16 + 1;
+        }
+        roll -= weight;
+    }
+    unreachable!("the roll is under the weights' sum")
+}
+
+pub(crate) fn sides(id: u16) -> u16 {
+    DICE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())[id as usize].weights.len() as u16
+}
+
+pub(crate) fn name(id: u16) -> String {
+    DICE.lock().unwrap_or_else(|poisoned| poisoned.into_inner())[id as usize].name.clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn defines_and_reads() {
+        let die = define("Tilted", "0, 0, 1").unwrap(); // only ever a 3
+        assert_eq!(define("tilted", "0 0 1"), Ok(die)); // the same die again
+        let (kind, rest) = take("2dtilted!+1").unwrap();
+        assert!(kind == die);
+        assert_eq!(rest, "2d3!+1");
+        assert!(take("2dtilt+1").is_none());
+        assert_eq!(take("dtiltedkh1").map(|(_, rest)| rest).as_deref(), Some("d3kh1"));
+        assert_eq!(die.to_string(), "tilted");
+        assert_eq!(die.as_number(), 3);
+        let mut rng = StdRng::seed_from_u64(1);
+        assert!((0..50).all(|_| die.flip(&mut rng) == 3));
+    }
+
+    #[test]
+    fn rolls_like_any_die() {
+        use crate::input_handling::{generate_dice, ThrowOptions};
+        let heavy = define("heavy", "0, 1").unwrap();
+        let (dice, command_log) = generate_dice(String::from("2dheavy!+1 # sure thing, d6"), &ThrowOptions::default()).unwrap();
+        assert!(dice[..2] == [heavy, heavy]);
+        assert_eq!(command_log[0].to_string(), "2dheavy!+1");
+        assert!(command_log[0].label.as_deref() == Some("sure thing"));
+        assert!(generate_dice(String::from("2dheavyr1"), &ThrowOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn rejects() {
+        assert!(define("x", "1, 2").is_err()); // would be read as a letter die
+        assert!(define("d20", "1, 2").is_err());
+        assert!(define("loot", "").is_err());
+        assert!(define("loot", "0, 0").is_err());
+        assert_eq!(define("loot", "1, two").err(), Some(DiceError::Malformed { part: "Weight" }));
+    }
+}